@@ -1,6 +1,8 @@
-use common::{job::JobStatus, message::{ErrorMessage, GetJobListResponse, GetJobStatusResponse, SubmitJobListRequest, SubmitJobRequest}};
+use common::{calendar::Calendar, job::{Job, JobStatus}, message::{BackfillRequest, BackfillResponse, DeadLetterBulkResponse, ErrorMessage, GetCalendarListResponse, GetDeadLetterListResponse, GetJobListResponse, GetJobStatusResponse, GetSchedulePreviewResponse, GetScheduleRunsResponse, PutCalendarRequest, SubmitJobListRequest, SubmitJobRequest, UpdateScheduleRequest}};
 use reqwest::{Response, StatusCode};
+use serde::de::DeserializeOwned;
 use std::sync::LazyLock;
+use uuid::Uuid;
 
 const TOO_MANY_REQUESTS: &str = "Slow down too many requests have been sent recently.";
 const PARSE_ERROR_STRING: &str = "Unknown message from server.";
@@ -89,4 +91,131 @@ pub async fn fetch_list(status_search: Option<JobStatus>) -> Result<GetJobListRe
         Err(_) => {Err(ErrorMessage::new(String::from("503"), FAILED_REQUEST_STRING.to_string()))}
     }
 
-}
\ No newline at end of file
+}
+
+// Shared handling for the newer endpoints, turns any non success status into the servers ErrorMessage
+async fn parse_response<T: DeserializeOwned>(response: Result<Response, reqwest::Error>) -> Result<T, ErrorMessage> {
+    match response {
+        Ok(response) => {
+            if response.status().is_success() {
+                let json = response.json::<T>().await
+                    .map_err(|_| ErrorMessage::new(String::from("500"), PARSE_ERROR_STRING.to_string()))?;
+
+                Ok(json)
+            } else if response.status() == StatusCode::TOO_MANY_REQUESTS {
+                Err(ErrorMessage::new(String::from("429"), TOO_MANY_REQUESTS.to_string()))
+            } else {
+                let error = response.json::<ErrorMessage>().await
+                    .unwrap_or_else(|_| ErrorMessage::new(String::from("500"), PARSE_ERROR_STRING.to_string()));
+                
+                Err(error)
+            }
+        },
+        Err(_) => {Err(ErrorMessage::new(String::from("503"), FAILED_REQUEST_STRING.to_string()))}
+    }
+}
+
+pub async fn cancel_job(id: String) -> Result<Job, ErrorMessage> {
+    let url = format!("http://{}/api/job/{}/cancel", *COORDINATOR_ADDR, id);
+
+    parse_response(reqwest::Client::new().post(&url).send().await).await
+}
+
+pub async fn fetch_dead_letters() -> Result<GetDeadLetterListResponse, ErrorMessage> {
+    let url = format!("http://{}/api/dlq", *COORDINATOR_ADDR);
+
+    parse_response(reqwest::get(&url).await).await
+}
+
+pub async fn requeue_dead_letter(id: Uuid) -> Result<Job, ErrorMessage> {
+    let url = format!("http://{}/api/dlq/{}/requeue", *COORDINATOR_ADDR, id);
+
+    parse_response(reqwest::Client::new().post(&url).send().await).await
+}
+
+pub async fn requeue_all_dead_letters() -> Result<DeadLetterBulkResponse, ErrorMessage> {
+    let url = format!("http://{}/api/dlq/requeue", *COORDINATOR_ADDR);
+
+    parse_response(reqwest::Client::new().post(&url).send().await).await
+}
+
+pub async fn purge_dead_letter(id: Uuid) -> Result<DeadLetterBulkResponse, ErrorMessage> {
+    let url = format!("http://{}/api/dlq/{}", *COORDINATOR_ADDR, id);
+
+    parse_response(reqwest::Client::new().delete(&url).send().await).await
+}
+
+pub async fn purge_all_dead_letters() -> Result<DeadLetterBulkResponse, ErrorMessage> {
+    let url = format!("http://{}/api/dlq", *COORDINATOR_ADDR);
+
+    parse_response(reqwest::Client::new().delete(&url).send().await).await
+}
+
+pub async fn update_schedule(id: Uuid, update: UpdateScheduleRequest) -> Result<Job, ErrorMessage> {
+    let url = format!("http://{}/api/schedule/{}", *COORDINATOR_ADDR, id);
+
+    parse_response(reqwest::Client::new().patch(&url).json(&update).send().await).await
+}
+
+pub async fn backfill_schedule(id: Uuid, backfill: BackfillRequest) -> Result<BackfillResponse, ErrorMessage> {
+    let url = format!("http://{}/api/schedule/{}/backfill", *COORDINATOR_ADDR, id);
+
+    parse_response(reqwest::Client::new().post(&url).json(&backfill).send().await).await
+}
+
+pub async fn pause_schedule(id: Uuid) -> Result<Job, ErrorMessage> {
+    let url = format!("http://{}/api/schedule/{}/pause", *COORDINATOR_ADDR, id);
+
+    parse_response(reqwest::Client::new().post(&url).send().await).await
+}
+
+pub async fn resume_schedule(id: Uuid) -> Result<Job, ErrorMessage> {
+    let url = format!("http://{}/api/schedule/{}/resume", *COORDINATOR_ADDR, id);
+
+    parse_response(reqwest::Client::new().post(&url).send().await).await
+}
+
+pub async fn delete_schedule(id: Uuid) -> Result<Job, ErrorMessage> {
+    let url = format!("http://{}/api/schedule/{}", *COORDINATOR_ADDR, id);
+
+    parse_response(reqwest::Client::new().delete(&url).send().await).await
+}
+
+pub async fn fetch_schedule_runs(id: Uuid, limit: Option<usize>) -> Result<GetScheduleRunsResponse, ErrorMessage> {
+    let url = match limit {
+        Some(limit) => format!("http://{}/api/schedule/{}/runs?limit={}", *COORDINATOR_ADDR, id, limit),
+        None => format!("http://{}/api/schedule/{}/runs", *COORDINATOR_ADDR, id)
+    };
+
+    parse_response(reqwest::get(&url).await).await
+}
+
+pub async fn fetch_schedule_preview(id: Uuid, n: usize) -> Result<GetSchedulePreviewResponse, ErrorMessage> {
+    let url = format!("http://{}/api/schedule/{}/preview?n={}", *COORDINATOR_ADDR, id, n);
+
+    parse_response(reqwest::get(&url).await).await
+}
+
+pub async fn fetch_calendars() -> Result<GetCalendarListResponse, ErrorMessage> {
+    let url = format!("http://{}/api/calendar", *COORDINATOR_ADDR);
+
+    parse_response(reqwest::get(&url).await).await
+}
+
+pub async fn fetch_calendar(name: &str) -> Result<Calendar, ErrorMessage> {
+    let url = format!("http://{}/api/calendar/{}", *COORDINATOR_ADDR, name);
+
+    parse_response(reqwest::get(&url).await).await
+}
+
+pub async fn put_calendar(name: &str, calendar: PutCalendarRequest) -> Result<Calendar, ErrorMessage> {
+    let url = format!("http://{}/api/calendar/{}", *COORDINATOR_ADDR, name);
+
+    parse_response(reqwest::Client::new().put(&url).json(&calendar).send().await).await
+}
+
+pub async fn delete_calendar(name: &str) -> Result<Calendar, ErrorMessage> {
+    let url = format!("http://{}/api/calendar/{}", *COORDINATOR_ADDR, name);
+
+    parse_response(reqwest::Client::new().delete(&url).send().await).await
+}
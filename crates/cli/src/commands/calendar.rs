@@ -0,0 +1,96 @@
+use chrono::{DateTime, Utc};
+use colored::*;
+use common::{calendar::{BlackoutWindow, Calendar}, duration::parse_duration, message::PutCalendarRequest};
+
+use crate::client;
+
+pub async fn list() {
+    match client::fetch_calendars().await {
+        Ok(response) => {
+            if response.list.is_empty() {
+                println!("There are no calendars.");
+                return;
+            }
+
+            for calendar in response.list {
+                print_calendar(calendar);
+            }
+        },
+        Err(error_message) => println!("Error code: {}. {}", error_message.code, error_message.message.red())
+    }
+}
+
+pub async fn show(name: String) {
+    match client::fetch_calendar(&name).await {
+        Ok(calendar) => print_calendar(calendar),
+        Err(error_message) => println!("Error code: {}. {}", error_message.code, error_message.message.red())
+    }
+}
+
+// Replaces every window the calendar had
+pub async fn set(name: String, ranges: Vec<String>, recurring: Vec<String>, timezone: Option<String>) {
+    let mut windows = vec![];
+
+    for range in ranges {
+        let parsed = range.split_once(',').and_then(|(start, end)| {
+            let start = DateTime::parse_from_rfc3339(start.trim()).ok()?.with_timezone(&Utc);
+            let end = DateTime::parse_from_rfc3339(end.trim()).ok()?.with_timezone(&Utc);
+            Some(BlackoutWindow::RANGE { start, end })
+        });
+
+        match parsed {
+            Some(window) => windows.push(window),
+            None => {
+                println!("Invalid --range {}, must be two RFC3339 times. Example: 2026-12-24T00:00:00Z,2026-12-27T00:00:00Z", range);
+                return;
+            }
+        }
+    }
+
+    for window in recurring {
+        let parsed = window.rsplit_once(" for ").and_then(|(cron, duration)| {
+            let duration = parse_duration(duration).ok().filter(|d| d.num_seconds() > 0)?;
+            Some(BlackoutWindow::RECURRING { cron: cron.trim().to_string(), duration_secs: duration.num_seconds() as u64, timezone: timezone.clone() })
+        });
+
+        match parsed {
+            Some(window) => windows.push(window),
+            None => {
+                println!("Invalid --recurring {}, must be a cron and a duration. Example: \"0 2 * * SUN for 2h\"", window);
+                return;
+            }
+        }
+    }
+
+    match client::put_calendar(&name, PutCalendarRequest { windows }).await {
+        Ok(calendar) => {
+            println!("Calendar {} has been {}", calendar.name.blue(), "saved".green());
+            print_calendar(calendar);
+        },
+        Err(error_message) => println!("Error code: {}. {}", error_message.code, error_message.message.red())
+    }
+}
+
+pub async fn delete(name: String) {
+    match client::delete_calendar(&name).await {
+        Ok(calendar) => println!("Calendar {} has been {}", calendar.name.blue(), "deleted".red()),
+        Err(error_message) => println!("Error code: {}. {}", error_message.code, error_message.message.red())
+    }
+}
+
+fn print_calendar(calendar: Calendar) {
+    println!("{} {}", "Calendar:".white(), calendar.name.blue());
+
+    for window in calendar.windows {
+        match window {
+            BlackoutWindow::RANGE { start, end } => println!("\tFrom {} until {}", start, end),
+            BlackoutWindow::RECURRING { cron, duration_secs, timezone } => println!("\tEvery \"{}\" ({}) for {}s", 
+                cron, 
+                timezone.unwrap_or_else(|| "UTC".to_string()), 
+                duration_secs
+            )
+        }
+    }
+
+    println!();
+}
@@ -0,0 +1,10 @@
+use colored::*;
+
+use crate::client;
+
+pub async fn job(id: String) {
+    match client::cancel_job(id).await {
+        Ok(job) => println!("Job with ID: {} has been {}", job.id.to_string().blue(), "canceled".red()),
+        Err(error_message) => println!("Error code: {}. {}", error_message.code, error_message.message.red())
+    }
+}
@@ -0,0 +1,75 @@
+use colored::*;
+use uuid::Uuid;
+
+use crate::client;
+
+pub async fn list() {
+    match client::fetch_dead_letters().await {
+        Ok(response) => {
+            if response.list.is_empty() {
+                println!("The dead letter queue is empty.");
+                return;
+            }
+
+            for letter in response.list {
+                println!("{} {}\n\tCommand: {} {:?}\n\tReason: {}\n\tFailed at (UTC): {}\n\tAttempts:",
+                    "Job ID:".white(),
+                    letter.job.id.to_string().blue(),
+                    letter.job.command.blue(),
+                    letter.job.args,
+                    letter.reason.red(),
+                    letter.failed_at.to_string().white()
+                );
+
+                for attempt in letter.attempts {
+                    println!("\t\t#{} Worker: {} Exit Code: {}{} Error: {}",
+                        attempt.attempt,
+                        attempt.worker_id,
+                        attempt.result.exitcode.to_string().red(),
+                        if attempt.result.timed_out { " (Timed out)".red() } else { "".white() },
+                        attempt.result.stderr.trim().red()
+                    );
+                }
+
+                println!();
+            }
+        },
+        Err(error_message) => println!("Error code: {}. {}", error_message.code, error_message.message.red())
+    }
+}
+
+pub async fn requeue(ids: Vec<Uuid>, all: bool) {
+    if all {
+        match client::requeue_all_dead_letters().await {
+            Ok(response) => println!("Requeued {} jobs from the dead letter queue", response.ids.len().to_string().green()),
+            Err(error_message) => println!("Error code: {}. {}", error_message.code, error_message.message.red())
+        }
+    } else if ids.is_empty() {
+        println!("{}", "Give at least one job ID or use --all".red());
+    } else {
+        for id in ids {
+            match client::requeue_dead_letter(id).await {
+                Ok(job) => println!("Requeued job with ID: {}", job.id.to_string().green()),
+                Err(error_message) => println!("Job ID: {} Error code: {}. {}", id, error_message.code, error_message.message.red())
+            }
+        }
+    }
+}
+
+pub async fn purge(ids: Vec<Uuid>, all: bool) {
+    if all {
+        match client::purge_all_dead_letters().await {
+            Ok(response) => println!("Purged {} jobs from the dead letter queue", response.ids.len().to_string().red()),
+            Err(error_message) => println!("Error code: {}. {}", error_message.code, error_message.message.red())
+        }
+    } else if ids.is_empty() {
+        println!("{}", "Give at least one job ID or use --all".red());
+    } else {
+        for id in ids {
+            match client::purge_dead_letter(id).await {
+                Ok(_) => println!("Purged job with ID: {}", id.to_string().red()),
+                Err(error_message) => println!("Job ID: {} Error code: {}. {}", id, error_message.code, error_message.message.red())
+            }
+        }
+    }
+}
@@ -1,3 +1,7 @@
+pub mod calendar;
+pub mod cancel;
+pub mod dlq;
 pub mod list;
+pub mod schedule;
 pub mod status;
 pub mod submit;
\ No newline at end of file
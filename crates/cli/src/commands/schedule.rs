@@ -0,0 +1,134 @@
+use std::str::FromStr;
+use colored::*;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use common::{job::{Job, JobStatus, Priority}, message::{BackfillRequest, ErrorMessage, UpdateScheduleRequest}};
+use uuid::Uuid;
+
+use crate::client;
+
+pub async fn pause(id: Uuid) {
+    print_result(client::pause_schedule(id).await, "paused".yellow());
+}
+
+pub async fn resume(id: Uuid) {
+    print_result(client::resume_schedule(id).await, "resumed".green());
+}
+
+pub async fn delete(id: Uuid) {
+    print_result(client::delete_schedule(id).await, "deleted".red());
+}
+
+pub async fn edit(id: Uuid, cron: Option<String>, every: Option<String>, command: Option<String>, args: Option<String>, priority: Option<String>) {
+    let priority = match priority {
+        Some(p) => match Priority::from_str(&p.to_uppercase()) {
+            Ok(p) => Some(p),
+            Err(_) => {
+                println!("Invalid priority value, must be one of the following: Low, Medium, High");
+                return;
+            }
+        },
+        None => None
+    };
+
+    let update = UpdateScheduleRequest {
+        schedule: cron,
+        every,
+        command,
+        args: args.map(|a| a.split_ascii_whitespace().map(|arg| arg.to_string()).collect()),
+        priority
+    };
+
+    match client::update_schedule(id, update).await {
+        Ok(job) => {
+            println!("Schedule with ID: {} has been {}", job.id.to_string().blue(), "updated".green());
+            println!("\tSchedule: {}\n\tCommand: {} {:?}\n\tPriority: {}\n\tNext run time: {}",
+                describe(&job),
+                job.command,
+                job.args,
+                job.priority.to_string(),
+                job.next_run.map(|t| t.to_string()).unwrap_or_else(|| "No next run".to_string())
+            );
+        },
+        Err(error_message) => println!("Error code: {}. {}", error_message.code, error_message.message.red())
+    }
+}
+
+pub async fn history(id: Uuid, limit: Option<usize>) {
+    match client::fetch_schedule_runs(id, limit).await {
+        Ok(response) => {
+            if response.runs.is_empty() {
+                println!("Schedule with ID: {} hasn't started any runs yet.", id.to_string().blue());
+                return;
+            }
+
+            println!("Runs of schedule {} (newest first):\n", id.to_string().blue());
+
+            for run in response.runs {
+                let status = match run.status {
+                    JobStatus::COMPLETED => run.status.to_string().green(),
+                    JobStatus::FAILED | JobStatus::CANCELED => run.status.to_string().red(),
+                    _ => run.status.to_string().yellow()
+                };
+
+                println!("{} {}\n\tScheduled for (UTC): {}\n\tCreated (UTC): {}\n\tAttempts: {}\n\tExit code: {}\n\tDuration: {}\n",
+                    run.job_id.to_string().white(),
+                    status,
+                    run.scheduled_for.map(|t| t.to_string()).unwrap_or_else(|| "None".to_string()),
+                    run.created_at,
+                    run.attempts,
+                    run.exitcode.map(|c| c.to_string()).unwrap_or_else(|| "None".to_string()),
+                    run.duration_ms.map(|ms| format!("{:.3}s", ms as f64 / 1000.0)).unwrap_or_else(|| "Not run yet".to_string())
+                );
+            }
+        },
+        Err(error_message) => println!("Error code: {}. {}", error_message.code, error_message.message.red())
+    }
+}
+
+pub async fn preview(id: Uuid, n: usize) {
+    match client::fetch_schedule_preview(id, n).await {
+        Ok(response) => {
+            let timezone = response.timezone.and_then(|tz| Tz::from_str(&tz).ok());
+
+            println!("Next {} runs of schedule {}:", response.next_runs.len(), id.to_string().blue());
+
+            for next_run in response.next_runs {
+                match timezone {
+                    Some(tz) => println!("\t{} ({})", next_run.to_string().green(), next_run.with_timezone(&tz)),
+                    None => println!("\t{}", next_run.to_string().green())
+                }
+            }
+        },
+        Err(error_message) => println!("Error code: {}. {}", error_message.code, error_message.message.red())
+    }
+}
+
+pub async fn backfill(id: Uuid, from: DateTime<Utc>, to: DateTime<Utc>, max_in_flight: Option<usize>) {
+    match client::backfill_schedule(id, BackfillRequest { from, to, max_in_flight }).await {
+        Ok(response) => {
+            println!("Queued {} backfill runs of schedule {}, {} at a time:", response.runs.len(), id.to_string().blue(), response.max_in_flight);
+
+            for run in response.runs {
+                println!("\t{}", run.to_string().green());
+            }
+        },
+        Err(error_message) => println!("Error code: {}. {}", error_message.code, error_message.message.red())
+    }
+}
+
+fn print_result(result: Result<Job, ErrorMessage>, action: ColoredString) {
+    match result {
+        Ok(job) => println!("Schedule with ID: {} has been {}", job.id.to_string().blue(), action),
+        Err(error_message) => println!("Error code: {}. {}", error_message.code, error_message.message.red())
+    }
+}
+
+// The cron, or the interval for schedules that run every N seconds
+pub fn describe(job: &Job) -> String {
+    match (&job.schedule, job.interval_secs) {
+        (Some(cron), _) => cron.clone(),
+        (None, Some(secs)) => format!("Every {}s from {}", secs, job.anchor.map(|t| t.to_string()).unwrap_or_else(|| "submission".to_string())),
+        (None, None) => "No schedule".to_string()
+    }
+}
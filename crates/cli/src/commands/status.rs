@@ -1,9 +1,9 @@
 use common::job::{JobStatus, Priority};
 use colored::*;
 
-use crate::client;
+use crate::{client, commands::schedule};
 
-pub async fn fetch(id: String) {
+pub async fn fetch(id: String, show_attempts: bool) {
     let res = client::fetch_status(id.clone()).await;
 
     if res.is_ok() {
@@ -11,7 +11,7 @@ pub async fn fetch(id: String) {
 
         // I hate these one line string
         let print_response = format!(
-            "Status for Job ID: {}\n\n{}\nPriority: {}\nRetry count: {}\n\nCommand: {}\nArguments: {:#?}\n\nTime Created (UTC): {}\n\n{}\n\n{}\n\n{}", 
+            "Status for Job ID: {}\n\n{}\nPriority: {}\nRetry count: {}\nNext attempt (UTC): {}\nTimeout: {}\n\nCommand: {}\nArguments: {:#?}\n\nTime Created (UTC): {}\nRun at (UTC): {}\nScheduled for (UTC): {}\n\n{}\n\n{}\n\n{}", 
             job_status_resp.job.id.to_string().blue(),
 
             if job_status_resp.job.status == JobStatus::CANCELED || job_status_resp.job.status == JobStatus::FAILED {
@@ -42,14 +42,39 @@ pub async fn fetch(id: String) {
             } else {
                 job_status_resp.job.retry_count.to_string().green()
             },
+            if let Some(next) = job_status_resp.job.next_attempt_at {
+                next.to_string().yellow()
+            } else {
+                "None".to_string().white()
+            },
+            if let Some(secs) = job_status_resp.job.timeout_secs {
+                format!("{} seconds", secs).white()
+            } else {
+                "None".to_string().white()
+            },
 
             job_status_resp.job.command.blue(),
             job_status_resp.job.args,
 
             job_status_resp.job.timestamp.to_utc().to_string().blue(),
+            if let Some(run_at) = job_status_resp.job.run_at {
+                run_at.to_string().blue()
+            } else {
+                "As soon as possible".to_string().white()
+            },
+            if let Some(scheduled_for) = job_status_resp.job.scheduled_for {
+                scheduled_for.to_string().blue()
+            } else {
+                "None".to_string().white()
+            },
 
             if let Some(result) = job_status_resp.result {
-                format!("Results: \n\tExit Code: {} \n\tOutput: {} \n\tError: {}", 
+                format!("Results: {}\n\tExit Code: {} \n\tOutput: {} \n\tError: {}", 
+                    if result.timed_out {
+                        "(Timed out)".red()
+                    } else {
+                        "".white()
+                    },
                     if result.exitcode == 0 {
                         result.exitcode.to_string().green()
                     } else {
@@ -60,7 +85,7 @@ pub async fn fetch(id: String) {
                 )
             } else if job_status_resp.job.is_recurring {
                 format!("{}",
-                    "Schedule jobs wont have results, find one if its spawned jobs.".blue()
+                    "Schedule jobs wont have results, see its runs with `scheduler schedule history <id>`.".blue()
                 )
             } else {
                 format!("{}",
@@ -77,8 +102,20 @@ pub async fn fetch(id: String) {
             },
 
             if job_status_resp.job.is_recurring {
-                format!("Schedule Info: \n\tSchedule: {} \n\tRecurring: {} \n\tNext run time {}", 
-                    if let Some(sched) = job_status_resp.job.schedule {sched} else {"No schedule".to_string()}, 
+                format!("Schedule Info: \n\tSchedule: {} \n\tJitter: {} \n\tTimezone: {} \n\tMisfire policy: {} \n\tConcurrency policy: {} \n\tRuns: {} \n\tNot before: {} \n\tNot after: {} \n\tCalendars: {} ({}) \n\tRecurring: {} \n\tNext run time {}", 
+                    schedule::describe(&job_status_resp.job),
+                    job_status_resp.job.jitter_secs.map(|s| format!("up to {}s", s)).unwrap_or_else(|| "None".to_string()),
+                    job_status_resp.job.timezone.unwrap_or_else(|| "UTC".to_string()),
+                    job_status_resp.job.misfire_policy,
+                    job_status_resp.job.concurrency_policy,
+                    match job_status_resp.job.max_runs {
+                        Some(max) => format!("{} of {}", job_status_resp.job.run_count, max),
+                        None => job_status_resp.job.run_count.to_string()
+                    },
+                    job_status_resp.job.not_before.map(|t| t.to_string()).unwrap_or_else(|| "None".to_string()),
+                    job_status_resp.job.not_after.map(|t| t.to_string()).unwrap_or_else(|| "None".to_string()),
+                    job_status_resp.job.calendars.map(|c| c.join(", ")).unwrap_or_else(|| "None".to_string()),
+                    job_status_resp.job.blackout_policy,
                     job_status_resp.job.is_recurring,
                     if let Some(next) = job_status_resp.job.next_run {next.to_string()} else {"No next run".to_string()}
                 ).green()
@@ -87,7 +124,33 @@ pub async fn fetch(id: String) {
             }
         );
 
-        println!("{}", print_response)
+        println!("{}", print_response);
+
+        if show_attempts {
+            println!("\nAttempts:");
+
+            if job_status_resp.attempts.is_empty() {
+                println!("\t{}", "No attempts yet".yellow());
+            }
+
+            for attempt in job_status_resp.attempts {
+                println!("\t#{} on worker {}\n\t\tStarted (UTC): {}\n\t\tFinished (UTC): {} ({}s)\n\t\tExit Code: {}{}\n\t\tOutput: {}\n\t\tError: {}",
+                    attempt.attempt,
+                    attempt.worker_id.to_string().blue(),
+                    attempt.started_at,
+                    attempt.finished_at,
+                    (attempt.finished_at - attempt.started_at).num_seconds(),
+                    if attempt.result.exitcode == 0 {
+                        attempt.result.exitcode.to_string().green()
+                    } else {
+                        attempt.result.exitcode.to_string().red()
+                    },
+                    if attempt.result.timed_out { " (Timed out)".red() } else { "".white() },
+                    attempt.result.stdout.trim().white(),
+                    attempt.result.stderr.trim().red()
+                );
+            }
+        }
     } else {
         let error_message = res.err().unwrap();
         println!("Error code: {}. {}", error_message.code, error_message.message.red())
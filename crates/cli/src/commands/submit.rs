@@ -1,12 +1,124 @@
 use std::str::FromStr;
+use chrono::{DateTime, Utc};
+use clap::Args;
 use uuid::Uuid;
 use colored::Colorize;
-use common::{job::{Job, Priority}, message::SubmitJobRequest};
+use common::{calendar::BlackoutPolicy, job::{ConcurrencyPolicy, Job, MisfirePolicy, Priority, RetryPolicy}, message::SubmitJobRequest};
 
 use crate::client;
 
+#[derive(Args)]
+pub struct SubmitArgs {
+    #[arg(help = "Command to run")]
+    command: String,
+
+    #[arg(long, help = "Arguments for the command")]
+    args: Option<String>,
+
+    #[arg(long, help = "Priority of the job. Options: High, Medium, or Low")]
+    priority: Option<String>,
+    
+    #[arg(long, group = "recurrence", help = "5-6 Length cron schedule")]
+    schedule: Option<String>,
+
+    #[arg(long, group = "recurrence", help = "Run every interval instead of on a cron\nExample: --every 90s, --every 4h")]
+    every: Option<String>,
+
+    #[arg(long, requires = "every", help = "RFC3339 time the intervals count from, defaults to now")]
+    anchor: Option<String>,
+
+    #[arg(long, requires = "recurrence", help = "Hold each run back by a random delay up to this long\nExample: --jitter 30s")]
+    jitter: Option<String>,
+
+    #[arg(long, requires = "schedule", help = "IANA timezone the schedule runs in, defaults to UTC\nExample: --timezone America/New_York")]
+    timezone: Option<String>,
+
+    #[arg(long, requires = "recurrence", help = "What to do about runs missed while the coordinator was down. Options: Skip, Run-Once, or Run-All")]
+    misfire: Option<String>,
+
+    #[arg(long, requires = "recurrence", help = "Most missed runs Run-All will start at once, defaults to 10")]
+    max_catchup: Option<u32>,
+
+    #[arg(long, requires = "recurrence", help = "What to do when the previous run is still going. Options: Allow, Forbid, or Replace")]
+    concurrency: Option<String>,
+
+    #[arg(long, requires = "recurrence", help = "Don't run the schedule before this RFC3339 time")]
+    not_before: Option<String>,
+
+    #[arg(long, requires = "recurrence", help = "Stop the schedule after this RFC3339 time")]
+    not_after: Option<String>,
+
+    #[arg(long, requires = "recurrence", help = "Stop the schedule after it has started this many runs")]
+    max_runs: Option<u32>,
+
+    #[arg(long, requires = "recurrence", value_delimiter(','), help = "Blackout calendars the schedule doesn't run in\nExample: --calendars holidays,maintenance")]
+    calendars: Option<Vec<String>>,
+
+    #[arg(long, requires = "calendars", help = "What to do with a run inside a blackout. Options: Skip, or Defer to the end of the blackout")]
+    blackout: Option<String>,
+
+    #[arg(long, value_delimiter(','), help = "UUID of job required to finish for this one to run\nExample: --depends-on UUID1, UUID2")]
+    depends_on: Option<Vec<Uuid>>,
+
+    #[arg(long, help = "Seconds the job may run before the worker kills it")]
+    timeout: Option<u64>,
+
+    #[arg(long, conflicts_with_all = ["delay", "recurrence"], help = "Run once at this RFC3339 time\nExample: --at 2026-01-31T09:00:00Z")]
+    at: Option<String>,
+
+    #[arg(long = "in", id = "delay", conflicts_with = "recurrence", help = "Run once after this long\nExample: --in 90s, --in 1h30m")]
+    delay: Option<String>,
+
+    #[command(flatten)]
+    retry: RetryArgs
+}
+
+#[derive(Args)]
+pub struct RetryArgs {
+    #[arg(long, help = "Total number of times the job may run, including the first run")]
+    max_attempts: Option<u32>,
+
+    #[arg(long, help = "Seconds to wait before the first retry")]
+    retry_delay: Option<u64>,
+
+    #[arg(long, help = "Multiplier applied to the delay after every retry")]
+    retry_multiplier: Option<f64>,
+
+    #[arg(long, help = "Longest delay in seconds between retries")]
+    retry_max_delay: Option<u64>,
+
+    #[arg(long, help = "Random spread of the delay as a fraction between 0 and 1")]
+    retry_jitter: Option<f64>,
+
+    #[arg(long, value_delimiter(','), help = "Only retry on these exit codes\nExample: --retry-exit-codes 1,75")]
+    retry_exit_codes: Option<Vec<i32>>
+}
+
+impl RetryArgs {
+    // Only sends a policy when a flag was given so the coordinator defaults apply otherwise
+    fn policy(self) -> Option<RetryPolicy> {
+        if self.max_attempts.is_none() && self.retry_delay.is_none() && self.retry_multiplier.is_none() 
+            && self.retry_max_delay.is_none() && self.retry_jitter.is_none() && self.retry_exit_codes.is_none() {
+            return None;
+        }
+
+        let default = RetryPolicy::default();
+
+        Some(RetryPolicy {
+            max_attempts: self.max_attempts.unwrap_or(default.max_attempts),
+            initial_delay_secs: self.retry_delay.unwrap_or(default.initial_delay_secs),
+            multiplier: self.retry_multiplier.unwrap_or(default.multiplier),
+            max_delay_secs: self.retry_max_delay.unwrap_or(default.max_delay_secs),
+            jitter: self.retry_jitter.unwrap_or(default.jitter),
+            retryable_exit_codes: self.retry_exit_codes
+        })
+    }
+}
+
+
+pub async fn job(submit_args: SubmitArgs) {
+    let SubmitArgs { command, args: args_str, priority, schedule, every, anchor, jitter, timezone, misfire, max_catchup, concurrency, not_before, not_after, max_runs, calendars, blackout, depends_on, timeout: timeout_secs, at, delay, retry } = submit_args;
 
-pub async fn job(command: String, args_str: Option<String>, priority: Option<String>, schedule: Option<String>, depends_on: Option<Vec<Uuid>>) {
     let mut args = vec![];
 
     if args_str.is_some() {
@@ -30,23 +142,95 @@ pub async fn job(command: String, args_str: Option<String>, priority: Option<Str
         p = Some(Priority::LOW);
     }
 
+    let misfire_policy = match misfire {
+        Some(m) => match MisfirePolicy::from_str(&m.to_uppercase().replace('-', "_")) {
+            Ok(policy) => Some(policy),
+            Err(_) => {
+                println!("Invalid misfire value, must be one of the following: Skip, Run-Once, Run-All");
+                return;
+            }
+        },
+        None => None
+    };
+
+    let concurrency_policy = match concurrency {
+        Some(c) => match ConcurrencyPolicy::from_str(&c.to_uppercase()) {
+            Ok(policy) => Some(policy),
+            Err(_) => {
+                println!("Invalid concurrency value, must be one of the following: Allow, Forbid, Replace");
+                return;
+            }
+        },
+        None => None
+    };
+
+    let blackout_policy = match blackout {
+        Some(b) => match BlackoutPolicy::from_str(&b.to_uppercase()) {
+            Ok(policy) => Some(policy),
+            Err(_) => {
+                println!("Invalid blackout value, must be one of the following: Skip, Defer");
+                return;
+            }
+        },
+        None => None
+    };
+
+    let (Some(run_at), Some(anchor), Some(not_before), Some(not_after)) = (parse_time("--at", at), parse_time("--anchor", anchor), parse_time("--not-before", not_before), parse_time("--not-after", not_after)) else {
+        return;
+    };
+
     let json = SubmitJobRequest {
         command: command,
         args: args,
         priority: p,
         schedule: schedule,
-        depends_on
+        every,
+        anchor,
+        jitter,
+        timezone,
+        misfire_policy,
+        max_catchup,
+        concurrency_policy,
+        not_before,
+        not_after,
+        max_runs,
+        calendars,
+        blackout_policy,
+        depends_on,
+        run_at,
+        delay,
+        timeout_secs,
+        retry_policy: retry.policy()
     };
 
     let result = client::submit_job(json).await;
     
     if let Ok(r) = result {
         match r.json::<Job>().await {
-            Ok(json) => println!("Job submited with ID: {}", json.id),
+            Ok(json) => {
+                println!("Job submited with ID: {}", json.id);
+
+                if let Some(run_at) = json.run_at {
+                    println!("It will run at (UTC): {}", run_at.to_string().blue());
+                }
+            },
             Err(_) => println!("{} Job was submited but failed to generate JSON response.", "Err:".red())
         }
     } else {
         let error_message = result.err().unwrap();
         println!("Error code: {}. {}", error_message.code, error_message.message.red())
     }
-}
\ No newline at end of file
+}
+// The outer None means the time was given but didn't parse
+fn parse_time(flag: &str, time: Option<String>) -> Option<Option<DateTime<Utc>>> {
+    match time {
+        Some(t) => match DateTime::parse_from_rfc3339(&t) {
+            Ok(t) => Some(Some(t.with_timezone(&Utc))),
+            Err(_) => {
+                println!("Invalid {} time, must be RFC3339. Example: 2026-01-31T09:00:00Z", flag);
+                None
+            }
+        },
+        None => Some(None)
+    }
+}
@@ -1,7 +1,8 @@
 use clap::{Parser, Subcommand};
+use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
-use crate::commands::{list, status, submit};
+use crate::commands::{calendar, cancel, dlq, list, schedule, status, submit::{self, SubmitArgs}};
 
 mod commands; mod client;
 
@@ -13,30 +14,20 @@ struct Cli {
     command: Commands,
 }
 
+// Parsed once at startup, the size of the Submit variant doesn't matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Commands {
     /// Submit new job
-    Submit {
-        #[arg(help = "Command to run")]
-        command: String,
-
-        #[arg(long, help = "Arguments for the command")]
-        args: Option<String>,
-
-        #[arg(long, help = "Priority of the job. Options: High, Medium, or Low")]
-        priority: Option<String>,
-        
-        #[arg(long, help = "5-6 Length cron schedule")]
-        schedule: Option<String>,
-
-        #[arg(long, value_delimiter(','), help = "UUID of job required to finish for this one to run\nExample: --depends-on UUID1, UUID2")]
-        depends_on: Option<Vec<Uuid>>
-    },
+    Submit(SubmitArgs),
     
     /// Check job status
     Status {
         #[arg(help = "UUID of job to lookup")]
         job_id: String,
+
+        #[arg(long, help = "Show every attempt the job has made")]
+        attempts: bool,
     },
     
     /// List jobs
@@ -44,6 +35,162 @@ enum Commands {
         #[arg(long, help = "Status to filter jobs")]
         status: Option<String>,
     },
+
+    /// Cancel a pending, waiting or running job
+    Cancel {
+        #[arg(help = "UUID of job to cancel")]
+        job_id: String,
+    },
+
+    /// Inspect and replay jobs that failed all their attempts
+    Dlq {
+        #[command(subcommand)]
+        action: DlqCommands,
+    },
+
+    /// Pause, resume, edit or delete a recurring job
+    Schedule {
+        #[command(subcommand)]
+        action: ScheduleCommands,
+    },
+
+    /// Manage blackout calendars schedules don't run in
+    Calendar {
+        #[command(subcommand)]
+        action: CalendarCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum CalendarCommands {
+    /// List every calendar with its blackout windows
+    List,
+
+    /// Show one calendar
+    Show {
+        #[arg(help = "Name of calendar")]
+        name: String,
+    },
+
+    /// Create a calendar or replace all of its windows
+    Set {
+        #[arg(help = "Name of calendar")]
+        name: String,
+
+        #[arg(long = "range", help = "Blackout between two RFC3339 times, can be repeated\nExample: --range 2026-12-24T00:00:00Z,2026-12-27T00:00:00Z")]
+        ranges: Vec<String>,
+
+        #[arg(long, help = "Blackout starting at every occurrence of a cron, can be repeated\nExample: --recurring \"0 2 * * SUN for 2h\"")]
+        recurring: Vec<String>,
+
+        #[arg(long, help = "IANA timezone the recurring windows are in, defaults to UTC")]
+        timezone: Option<String>,
+    },
+
+    /// Delete a calendar no schedule uses anymore
+    Delete {
+        #[arg(help = "Name of calendar")]
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum DlqCommands {
+    /// List dead lettered jobs with every attempt's result
+    List,
+
+    /// Put dead lettered jobs back in the queue with a fresh set of attempts
+    Requeue {
+        #[arg(help = "UUIDs of jobs to requeue")]
+        job_ids: Vec<Uuid>,
+
+        #[arg(long, help = "Requeue every job in the dead letter queue")]
+        all: bool,
+    },
+
+    /// Remove jobs from the dead letter queue without running them
+    Purge {
+        #[arg(help = "UUIDs of jobs to purge")]
+        job_ids: Vec<Uuid>,
+
+        #[arg(long, help = "Purge every job in the dead letter queue")]
+        all: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ScheduleCommands {
+    /// Stop a schedule from starting new runs
+    Pause {
+        #[arg(help = "UUID of schedule to pause")]
+        schedule_id: Uuid,
+    },
+
+    /// Start a paused schedule again from its next run after now
+    Resume {
+        #[arg(help = "UUID of schedule to resume")]
+        schedule_id: Uuid,
+    },
+
+    /// Change the cron, command, arguments or priority of a schedule
+    Edit {
+        #[arg(help = "UUID of schedule to edit")]
+        schedule_id: Uuid,
+
+        #[arg(long, conflicts_with = "every", help = "New 5-6 Length cron schedule")]
+        cron: Option<String>,
+
+        #[arg(long, help = "Run every interval instead\nExample: --every 90s")]
+        every: Option<String>,
+
+        #[arg(long, help = "New command to run")]
+        command: Option<String>,
+
+        #[arg(long, help = "New arguments for the command")]
+        args: Option<String>,
+
+        #[arg(long, help = "New priority. Options: High, Medium, or Low")]
+        priority: Option<String>,
+    },
+
+    /// Show the jobs a schedule started with their outcome and duration
+    History {
+        #[arg(help = "UUID of schedule to look up")]
+        schedule_id: Uuid,
+
+        #[arg(long, help = "Most runs to show, defaults to 20")]
+        limit: Option<usize>,
+    },
+
+    /// Show when a schedule will run next
+    Preview {
+        #[arg(help = "UUID of schedule to look up")]
+        schedule_id: Uuid,
+
+        #[arg(short, default_value_t = 10, help = "Number of upcoming runs to show")]
+        n: usize,
+    },
+
+    /// Run a schedule for every time it would have fired in a past range
+    Backfill {
+        #[arg(help = "UUID of schedule to backfill")]
+        schedule_id: Uuid,
+
+        #[arg(long, help = "RFC3339 start of the range, inclusive\nExample: --from 2026-09-01T00:00:00Z")]
+        from: DateTime<Utc>,
+
+        #[arg(long, help = "RFC3339 end of the range, inclusive")]
+        to: DateTime<Utc>,
+
+        #[arg(long, help = "Most backfill runs queued or running at once, defaults to 5")]
+        max_in_flight: Option<usize>,
+    },
+
+    /// Stop a schedule for good, runs it already started are left alone
+    Delete {
+        #[arg(help = "UUID of schedule to delete")]
+        schedule_id: Uuid,
+    },
 }
 
 #[tokio::main]
@@ -53,18 +200,41 @@ async fn main() {
     let cli = Cli::parse();
 
     match cli.command {
-        Commands::Submit { command , args, priority, schedule, depends_on} => { 
-            submit::job(
-                command, 
-                args, 
-                priority, 
-                schedule,
-                depends_on
-            ).await;
+        Commands::Submit(submit_args) => { submit::job(submit_args).await; },
+
+        Commands::Status { job_id, attempts } => { status::fetch(job_id, attempts).await; },
+
+        Commands::List { status } => { list::jobs(status).await; },
+
+        Commands::Cancel { job_id } => { cancel::job(job_id).await; },
+
+        Commands::Dlq { action } => {
+            match action {
+                DlqCommands::List => { dlq::list().await; },
+                DlqCommands::Requeue { job_ids, all } => { dlq::requeue(job_ids, all).await; },
+                DlqCommands::Purge { job_ids, all } => { dlq::purge(job_ids, all).await; }
+            }
         },
 
-        Commands::Status { job_id } => { status::fetch(job_id).await; },
+        Commands::Calendar { action } => {
+            match action {
+                CalendarCommands::List => { calendar::list().await; },
+                CalendarCommands::Show { name } => { calendar::show(name).await; },
+                CalendarCommands::Set { name, ranges, recurring, timezone } => { calendar::set(name, ranges, recurring, timezone).await; },
+                CalendarCommands::Delete { name } => { calendar::delete(name).await; }
+            }
+        },
 
-        Commands::List { status } => { list::jobs(status).await; }
+        Commands::Schedule { action } => {
+            match action {
+                ScheduleCommands::Pause { schedule_id } => { schedule::pause(schedule_id).await; },
+                ScheduleCommands::Resume { schedule_id } => { schedule::resume(schedule_id).await; },
+                ScheduleCommands::Edit { schedule_id, cron, every, command, args, priority } => { schedule::edit(schedule_id, cron, every, command, args, priority).await; },
+                ScheduleCommands::History { schedule_id, limit } => { schedule::history(schedule_id, limit).await; },
+                ScheduleCommands::Preview { schedule_id, n } => { schedule::preview(schedule_id, n).await; },
+                ScheduleCommands::Backfill { schedule_id, from, to, max_in_flight } => { schedule::backfill(schedule_id, from, to, max_in_flight).await; },
+                ScheduleCommands::Delete { schedule_id } => { schedule::delete(schedule_id).await; }
+            }
+        }
     }
 }
\ No newline at end of file
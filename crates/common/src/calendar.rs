@@ -0,0 +1,61 @@
+use serde::{
+    Deserialize, 
+    Serialize
+};
+use chrono::{
+    DateTime, 
+    Utc
+};
+use std::{fmt, str::FromStr};
+
+// Named set of blackout windows schedules can reference
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Calendar {
+    pub name: String,
+    pub windows: Vec<BlackoutWindow>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum BlackoutWindow {
+    // From start up to, but not including, end
+    RANGE {
+        start: DateTime<Utc>,
+        end: DateTime<Utc>
+    },
+    // Starts at every occurrence of the cron and lasts duration_secs
+    RECURRING {
+        cron: String,
+        duration_secs: u64,
+        timezone: Option<String>
+    }
+}
+
+// What a schedule does with an occurrence that falls in a blackout
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub enum BlackoutPolicy {
+    #[default]
+    SKIP,
+    DEFER // Run it once the blackout ends
+}
+
+impl FromStr for BlackoutPolicy {
+    type Err = &'static str;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "SKIP" => Ok(BlackoutPolicy::SKIP),
+            "DEFER" => Ok(BlackoutPolicy::DEFER),
+
+            _ => Err("Invalid Blackout Policy")
+        }
+    }
+}
+
+impl fmt::Display for BlackoutPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::SKIP => write!(f, "SKIP"),
+            Self::DEFER => write!(f, "DEFER")
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use chrono::Duration;
+
+// Parses durations like "90s", "15m", "4h", "1d" or combinations such as "1h30m", a bare number is seconds
+pub fn parse_duration(input: &str) -> Result<Duration, &'static str> {
+    let input = input.trim();
+
+    if input.is_empty() {
+        return Err("Empty duration");
+    }
+
+    if let Ok(secs) = input.parse::<i64>() {
+        return Duration::try_seconds(secs).ok_or("Duration is too large");
+    }
+
+    let mut total = Duration::zero();
+    let mut number = String::new();
+
+    for c in input.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+
+        let value = number.parse::<i64>().map_err(|_| "Duration unit is missing a number")?;
+        number.clear();
+
+        let part = match c {
+            's' => Duration::try_seconds(value),
+            'm' => Duration::try_minutes(value),
+            'h' => Duration::try_hours(value),
+            'd' => Duration::try_days(value),
+            'w' => Duration::try_weeks(value),
+
+            _ => return Err("Invalid duration unit, use s, m, h, d or w")
+        };
+
+        total = part.and_then(|part| total.checked_add(&part)).ok_or("Duration is too large")?;
+    }
+
+    if !number.is_empty() {
+        return Err("Duration number is missing a unit");
+    }
+
+    Ok(total)
+}
@@ -6,9 +6,11 @@ use chrono::{
     DateTime, 
     Utc
 };
-use std::str::FromStr;
+use std::{collections::HashMap, fmt, str::FromStr};
 use uuid::Uuid;
 
+use crate::calendar::BlackoutPolicy;
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Job {
     pub id: Uuid,
@@ -19,15 +21,40 @@ pub struct Job {
 
     pub retry_count: u32,
     pub max_retries: u32,
+    pub retry_policy: RetryPolicy,
+    pub next_attempt_at: Option<DateTime<Utc>>,
 
     pub priority: Priority,
 
     pub schedule: Option<String>,
+    pub interval_secs: Option<u64>, // Set instead of a cron for schedules that run every N seconds
+    pub anchor: Option<DateTime<Utc>>, // Interval runs land on anchor + k * interval
+    pub jitter_secs: Option<u64>, // Each run is held back a random 0..=jitter seconds
+    pub timezone: Option<String>, // IANA name the cron is evaluated in, None is UTC
+    pub misfire_policy: MisfirePolicy,
+    pub max_catchup: u32, // Most missed runs RUN_ALL will start at once
+    pub concurrency_policy: ConcurrencyPolicy,
+    pub not_before: Option<DateTime<Utc>>,
+    pub not_after: Option<DateTime<Utc>>,
+    pub max_runs: Option<u32>,
+    pub run_count: u32, // Runs the schedule has started
+    pub calendars: Option<Vec<String>>, // Names of blackout calendars
+    pub blackout_policy: BlackoutPolicy,
     pub next_run: Option<DateTime<Utc>>,
     pub is_recurring: bool,
     pub parent_schedule_id: Option<Uuid>,
+    pub scheduled_for: Option<DateTime<Utc>>, // Occurrence of the parent schedule this run stands for
+
+    pub depends_on: Option<Vec<Uuid>>,
+
+    pub run_at: Option<DateTime<Utc>>,
+
+    pub timeout_secs: Option<u64>,
 
-    pub depends_on: Option<Vec<Uuid>>
+    pub env: Option<HashMap<String, String>>, // Extra environment variables the worker sets for the process
+
+    pub lease_token: Option<u64>,
+    pub lease_expires_at: Option<DateTime<Utc>>
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -35,6 +62,52 @@ pub struct JobResult {
     pub exitcode: i32,
     pub stdout: String,
     pub stderr: String,
+
+    #[serde(default)]
+    pub timed_out: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct JobAttempt {
+    pub attempt: u32, // Counts up across retries and requeues, starting at 1
+    pub worker_id: Uuid,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub result: JobResult
+}
+
+// Missing fields fall back to the defaults, so a request can set only what it cares about
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct RetryPolicy {
+    pub max_attempts: u32, // Includes the first run
+    pub initial_delay_secs: u64,
+    pub multiplier: f64,
+    pub max_delay_secs: u64,
+    pub jitter: f64, // Fraction of the delay, 0.0 - 1.0
+    pub retryable_exit_codes: Option<Vec<i32>> // None retries on any non zero exit code
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_delay_secs: 0,
+            multiplier: 2.0,
+            max_delay_secs: 3600,
+            jitter: 0.0,
+            retryable_exit_codes: None
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn is_retryable(&self, exitcode: i32) -> bool {
+        match &self.retryable_exit_codes {
+            Some(codes) => codes.contains(&exitcode),
+            None => exitcode != 0
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -45,7 +118,9 @@ pub enum JobStatus {
     FAILED,
     CANCELED,
     RETRYING,
-    WAITING
+    WAITING,
+    SCHEDULED,
+    PAUSED
 }
 
 impl FromStr for JobStatus {
@@ -60,6 +135,8 @@ impl FromStr for JobStatus {
             "CANCELED" => Ok(JobStatus::CANCELED),
             "RETRYING" => Ok(JobStatus::RETRYING),
             "WAITING" => Ok(JobStatus::WAITING),
+            "SCHEDULED" => Ok(JobStatus::SCHEDULED),
+            "PAUSED" => Ok(JobStatus::PAUSED),
 
             _ => Err("Invalid Status")
         }
@@ -76,7 +153,9 @@ impl ToString for JobStatus {
             Self::FAILED => "FAILED".to_string(),
             Self::CANCELED => "CANCELED".to_string(),
             Self::RETRYING => "RETRYING".to_string(),
-            Self::WAITING => "WAITING".to_string()
+            Self::WAITING => "WAITING".to_string(),
+            Self::SCHEDULED => "SCHEDULED".to_string(),
+            Self::PAUSED => "PAUSED".to_string()
         }
     }
 }
@@ -110,4 +189,70 @@ impl ToString for Priority {
             Self::HIGH => "HIGH".to_string()
         }
     }
-}
\ No newline at end of file
+}
+// What a schedule does about runs it missed while the coordinator was down
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+#[allow(non_camel_case_types)]
+pub enum MisfirePolicy {
+    #[default]
+    SKIP,
+    RUN_ONCE,
+    RUN_ALL
+}
+
+impl FromStr for MisfirePolicy {
+    type Err = &'static str;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "SKIP" => Ok(MisfirePolicy::SKIP),
+            "RUN_ONCE" => Ok(MisfirePolicy::RUN_ONCE),
+            "RUN_ALL" => Ok(MisfirePolicy::RUN_ALL),
+
+            _ => Err("Invalid Misfire Policy")
+        }
+    }
+}
+
+impl fmt::Display for MisfirePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::SKIP => write!(f, "SKIP"),
+            Self::RUN_ONCE => write!(f, "RUN_ONCE"),
+            Self::RUN_ALL => write!(f, "RUN_ALL")
+        }
+    }
+}
+
+// What a schedule does when its previous run hasn't finished by the next occurrence
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub enum ConcurrencyPolicy {
+    #[default]
+    ALLOW,
+    FORBID, // Skip this occurrence
+    REPLACE // Cancel the previous run and start a new one
+}
+
+impl FromStr for ConcurrencyPolicy {
+    type Err = &'static str;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "ALLOW" => Ok(ConcurrencyPolicy::ALLOW),
+            "FORBID" => Ok(ConcurrencyPolicy::FORBID),
+            "REPLACE" => Ok(ConcurrencyPolicy::REPLACE),
+
+            _ => Err("Invalid Concurrency Policy")
+        }
+    }
+}
+
+impl fmt::Display for ConcurrencyPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ALLOW => write!(f, "ALLOW"),
+            Self::FORBID => write!(f, "FORBID"),
+            Self::REPLACE => write!(f, "REPLACE")
+        }
+    }
+}
@@ -1,2 +1,4 @@
+pub mod calendar;
+pub mod duration;
 pub mod job;
 pub mod message;
\ No newline at end of file
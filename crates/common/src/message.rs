@@ -6,10 +6,12 @@ use chrono::{
     DateTime, 
     Utc
 };
+use std::collections::HashSet;
 use uuid::Uuid;
 
+use crate::calendar::{BlackoutPolicy, BlackoutWindow, Calendar};
 use crate::job::{
-    Job, JobResult, JobStatus, Priority 
+    ConcurrencyPolicy, Job, JobAttempt, JobResult, JobStatus, MisfirePolicy, Priority, RetryPolicy 
 };
 
 // Client -> Coord 
@@ -22,8 +24,27 @@ pub struct SubmitJobRequest {
     pub priority: Option<Priority>,
 
     pub schedule: Option<String>,
-
-    pub depends_on: Option<Vec<Uuid>>
+    pub every: Option<String>, // Interval instead of a cron, e.g. "90s" or "4h"
+    pub anchor: Option<DateTime<Utc>>, // First interval run, defaults to the submission time
+    pub jitter: Option<String>, // Max random delay added to each run, e.g. "30s"
+    pub timezone: Option<String>, // IANA name, e.g. "Europe/London"
+    pub misfire_policy: Option<MisfirePolicy>,
+    pub max_catchup: Option<u32>,
+    pub concurrency_policy: Option<ConcurrencyPolicy>,
+    pub not_before: Option<DateTime<Utc>>,
+    pub not_after: Option<DateTime<Utc>>,
+    pub max_runs: Option<u32>,
+    pub calendars: Option<Vec<String>>,
+    pub blackout_policy: Option<BlackoutPolicy>,
+
+    pub depends_on: Option<Vec<Uuid>>,
+
+    pub run_at: Option<DateTime<Utc>>,
+    pub delay: Option<String>, // Relative to submission, e.g. "90s" or "1h30m"
+
+    pub timeout_secs: Option<u64>,
+
+    pub retry_policy: Option<RetryPolicy>
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -34,7 +55,10 @@ pub struct SubmitJobResponse {
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct GetJobStatusResponse {
     pub job: Job,
-    pub result: Option<JobResult>
+    pub result: Option<JobResult>,
+
+    #[serde(default)]
+    pub attempts: Vec<JobAttempt>
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -47,6 +71,95 @@ pub struct GetJobListResponse {
     pub list: Option<Vec<Job>>,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DeadLetter {
+    pub job: Job,
+    pub reason: String,
+    pub failed_at: DateTime<Utc>,
+    pub attempts: Vec<JobAttempt>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GetDeadLetterListResponse {
+    pub list: Vec<DeadLetter>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DeadLetterBulkResponse {
+    pub ids: Vec<Uuid>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ScheduleRunsQuery {
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SchedulePreviewQuery {
+    pub n: Option<usize>,
+}
+
+// One job a schedule started, timings span from the first attempt's start to the last one's finish
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ScheduleRun {
+    pub job_id: Uuid,
+    pub status: JobStatus,
+    pub created_at: DateTime<Utc>,
+    pub scheduled_for: Option<DateTime<Utc>>,
+    pub attempts: u32,
+    pub exitcode: Option<i32>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub duration_ms: Option<i64>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GetScheduleRunsResponse {
+    pub schedule_id: Uuid,
+    pub runs: Vec<ScheduleRun>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GetSchedulePreviewResponse {
+    pub schedule_id: Uuid,
+    pub timezone: Option<String>,
+    pub next_runs: Vec<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BackfillRequest {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub max_in_flight: Option<usize>, // Most backfill runs of the schedule queued or running at once
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BackfillResponse {
+    pub schedule_id: Uuid,
+    pub runs: Vec<DateTime<Utc>>, // Logical time of every run queued
+    pub max_in_flight: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PutCalendarRequest {
+    pub windows: Vec<BlackoutWindow>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GetCalendarListResponse {
+    pub list: Vec<Calendar>,
+}
+
+// Only the given fields are changed
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct UpdateScheduleRequest {
+    pub schedule: Option<String>,
+    pub every: Option<String>,
+    pub command: Option<String>,
+    pub args: Option<Vec<String>>,
+    pub priority: Option<Priority>
+}
+
 // Worker -> Coord
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -55,7 +168,8 @@ pub struct WorkerInfo {
     pub hostname: String,
     pub last_seen: DateTime<Utc>,
     pub status: WorkerStatus,
-    pub current_job_id: Option<Uuid>
+    pub current_job_ids: HashSet<Uuid>,
+    pub capacity: usize
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -67,13 +181,24 @@ pub enum WorkerStatus {
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct WorkerRegister {
     pub worker_id: Uuid,
-    pub hostname: String
+    pub hostname: String,
+    pub capacity: usize
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct WorkerHeartbeat {
     pub worker_id: Uuid,
-    pub timestamp: DateTime<Utc>
+    pub timestamp: DateTime<Utc>,
+
+    // Leases on the jobs the worker is still running, renewed on every heartbeat
+    #[serde(default)]
+    pub leases: Vec<JobLease>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct JobLease {
+    pub job_id: Uuid,
+    pub lease_token: u64
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -86,11 +211,18 @@ pub struct JobResultReport {
     pub job_id: Uuid,
     pub worker_id: Uuid,
     pub job_result: JobResult,
-    pub finished_at: DateTime<Utc>
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub lease_token: u64
 }
 
 // Coord -> Worker
 
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct WorkerHeartbeatResponse {
+    pub cancel_jobs: Vec<Uuid>
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct NextJobResponse {
     pub job: Option<Job>
@@ -1,9 +1,11 @@
 use common::{
+    calendar::{BlackoutWindow, Calendar},
+    duration::parse_duration,
     job::{
         Job, JobResult, JobStatus, Priority 
     }, 
     message::{
-        ErrorMessage, GetJobListResponse, NextJobRequest, SubmitJobListRequest, SubmitJobRequest, WorkerHeartbeat, WorkerInfo, WorkerRegister, WorkerStatus 
+        BackfillRequest, BackfillResponse, DeadLetterBulkResponse, ErrorMessage, GetCalendarListResponse, GetDeadLetterListResponse, GetJobListResponse, GetSchedulePreviewResponse, GetScheduleRunsResponse, JobResultReport, NextJobRequest, PutCalendarRequest, SchedulePreviewQuery, ScheduleRunsQuery, SubmitJobListRequest, SubmitJobRequest, UpdateScheduleRequest, WorkerHeartbeat, WorkerHeartbeatResponse, WorkerInfo, WorkerRegister, WorkerStatus 
     }
 };
 use actix_web::{
@@ -11,15 +13,23 @@ use actix_web::{
     Responder, 
     web
 };
-use cron::Schedule;
-use tokio::sync::Mutex;
-use std::{str::FromStr, sync::{Arc, LazyLock}};
-use chrono::Utc;
+use std::{collections::HashSet, str::FromStr, sync::{Arc, LazyLock}};
+use chrono::{DateTime, Duration, Utc};
 use uuid::Uuid;
 
-use crate::queue::JobQueue;
+use crate::{queue::{CalendarError, CancelError, JobQueue, MAX_BACKFILL_RUNS, MAX_RETRY_DELAY_SECS, ResultOutcome, ScheduleError}, schedule};
 
-const MAX_RETRIES: u32 = 3;
+// Most missed runs a RUN_ALL schedule starts after downtime unless the request says otherwise
+const DEFAULT_MAX_CATCHUP: u32 = 10;
+
+const DEFAULT_RUNS_LIMIT: usize = 20;
+const DEFAULT_PREVIEW_COUNT: usize = 10;
+const MAX_SCHEDULE_LIST: usize = 500;
+const DEFAULT_BACKFILL_IN_FLIGHT: usize = 5;
+// Longest delay a job can be submitted with, a year
+const MAX_DELAY_SECS: i64 = 365 * 24 * 60 * 60;
+// Longest a job can be given to run, a week
+const MAX_TIMEOUT_SECS: u64 = 7 * 24 * 60 * 60;
 
 static MAX_QUEUE_SIZE: LazyLock<usize> = LazyLock::new(|| {
     let queue_max= std::env::var("MAX_QUEUE_SIZE");
@@ -58,41 +68,36 @@ pub async fn metrics() -> impl Responder {
 
 // Worker
 
-pub async fn check_workers(queue: Arc<Mutex<JobQueue>>) {
-    let mut q = queue.lock().await;
-
-    JobQueue::check_worker(&mut q);
+pub async fn check_workers(queue: Arc<JobQueue>) {
+    JobQueue::check_worker(&queue);
 }
 
 pub async fn register_worker(
     req: web::Json<WorkerRegister>,
-    queue: web::Data<Arc<Mutex<JobQueue>>>
+    queue: web::Data<Arc<JobQueue>>
 ) -> impl Responder {
-    let mut q = queue.lock().await;
-
     let worker = WorkerInfo {
         worker_id: req.worker_id,
         hostname: req.hostname.clone(),
         last_seen: Utc::now(),
         status: WorkerStatus::ALIVE,
-        current_job_id: None
+        current_job_ids: HashSet::new(),
+        capacity: req.capacity.max(1)
     };
 
-    log::info!("New worker regestered. Hostname: {} and ID: {}", req.worker_id.clone(), req.hostname.clone());
+    log::info!("New worker regestered. Hostname: {} and ID: {} with {} slots", req.worker_id.clone(), req.hostname.clone(), worker.capacity);
 
-    JobQueue::register_worker(&mut q, worker.clone());
+    JobQueue::register_worker(&queue, worker.clone());
     HttpResponse::Ok().json(worker)
 }
 
 pub async fn worker_heartbeat(
     req: web::Json<WorkerHeartbeat>,
-    queue: web::Data<Arc<Mutex<JobQueue>>>
+    queue: web::Data<Arc<JobQueue>>
 ) -> impl Responder {
-    let mut q = queue.lock().await;
-
-    if JobQueue::is_worker_registered(&q, req.worker_id) {
-        JobQueue::update_worker_heartbeat(&mut q, req.clone());
-        HttpResponse::Ok().finish()
+    if JobQueue::is_worker_registered(&queue, req.worker_id) {
+        let cancel_jobs = JobQueue::update_worker_heartbeat(&queue, req.clone());
+        HttpResponse::Ok().json(WorkerHeartbeatResponse { cancel_jobs })
     } else {
         HttpResponse::NotFound().json(ErrorMessage::new(String::from("404"), String::from("Worker not registered.")))
     }
@@ -101,13 +106,11 @@ pub async fn worker_heartbeat(
 
 pub async fn next_job(
     req: web::Json<NextJobRequest>,
-    queue: web::Data<Arc<Mutex<JobQueue>>>
+    queue: web::Data<Arc<JobQueue>>
 ) -> impl Responder {
-    let mut q = queue.lock().await;
-
     log::info!("A worker has polled a new job.");
 
-    match JobQueue::get_next_job(&mut q, req.worker_id) {
+    match JobQueue::get_next_job(&queue, req.worker_id) {
         Some(result) => HttpResponse::Ok().json(result),
         None => HttpResponse::NotFound().body("No job in queue")
     }
@@ -117,40 +120,40 @@ pub async fn next_job(
 
 pub async fn submit_job(
     req: web::Json<SubmitJobRequest>,
-    queue: web::Data<Arc<Mutex<JobQueue>>>
+    queue: web::Data<Arc<JobQueue>>
 ) -> impl Responder {
-    let mut q = queue.lock().await;
-
     let mut fail_request = false;
 
-    #[allow(irrefutable_let_patterns)]
-    let (schedule, next_run, is_recurring) = if req.schedule.is_some() {
-        let req_sched = req.schedule.clone().unwrap();
+    let interval_secs = req.every.as_deref().map(schedule::parse_interval);
+    let jitter = req.jitter.as_deref().map(parse_duration);
+
+    let invalid_schedule_option = match schedule::parse_timezone(req.timezone.as_deref()) {
+        Err(err) => Some(err),
+        Ok(_) if req.schedule.is_some() && req.every.is_some() => Some(String::from("Only one of schedule and every can be given.")),
+        Ok(_) if interval_secs == Some(None) => Some(String::from("Invalid interval, must be a duration between 1 second and a year.")),
+        Ok(_) if req.every.is_some() && req.timezone.is_some() => Some(String::from("A timezone only applies to cron schedules.")),
+        Ok(_) if req.every.is_none() && req.anchor.is_some() => Some(String::from("An anchor can only be given with an interval.")),
+        Ok(_) if jitter.as_ref().is_some_and(|j| !j.as_ref().is_ok_and(|d| *d >= Duration::zero() && d.num_seconds() as u64 <= schedule::MAX_JITTER_SECS)) => {
+            Some(String::from("Invalid jitter, must be a non-negative duration of at most a day."))
+        },
+        Ok(_) if req.schedule.is_none() && req.every.is_none() && (req.jitter.is_some() || req.timezone.is_some() || req.misfire_policy.is_some() || req.max_catchup.is_some() || req.concurrency_policy.is_some()
+            || req.not_before.is_some() || req.not_after.is_some() || req.max_runs.is_some() || req.calendars.is_some() || req.blackout_policy.is_some()) => {
+            Some(String::from("A timezone, jitter, misfire policy, catch-up limit, concurrency policy, bounds, run limit or calendars can only be given with a schedule."))
+        },
+        Ok(_) if req.calendars.as_ref().is_some_and(|names| !JobQueue::missing_calendars(&queue, names).is_empty()) => {
+            Some(format!("Unknown calendars: {}", JobQueue::missing_calendars(&queue, req.calendars.as_deref().unwrap_or_default()).join(", ")))
+        },
+        Ok(_) if req.max_catchup == Some(0) => Some(String::from("The catch-up limit must be at least 1.")),
+        Ok(_) if req.max_runs == Some(0) => Some(String::from("The run limit must be at least 1.")),
+        Ok(_) if req.not_before.zip(req.not_after).is_some_and(|(not_before, not_after)| not_before >= not_after) => {
+            Some(String::from("not_before must be earlier than not_after."))
+        },
+        Ok(_) => None
+    };
 
-        let valid_sched = Schedule::from_str(&req_sched);
-        
-        let five_sched = if req_sched.split_whitespace().count() == 5 {
-            Schedule::from_str(&format!("0 {}", &req_sched)).is_ok()
-        } else { false };
-
-        if let sched = req_sched && (valid_sched.is_ok() || five_sched) {
-            let cron_expr = if five_sched {
-                format!("0 {}", sched)
-            } else {
-                sched.to_string()
-            };
-
-            let next = Schedule::from_str(&cron_expr)
-                .ok()
-                .and_then(|s| s.upcoming(Utc).next());
-        
-            (Some(cron_expr), next, true)
-        }
-        else {
-            (None, None, false)
-        }
-    } else {
-        (None, None, false)
+    let (schedule, is_recurring) = match req.schedule.as_deref().and_then(schedule::normalize_cron) {
+        Some(cron_expr) => (Some(cron_expr), true),
+        None => (None, interval_secs.flatten().is_some())
     };
 
     let depend = if req.depends_on.is_some() {
@@ -158,7 +161,7 @@ pub async fn submit_job(
 
         let mut count = vec![];
         for id in ids.as_ref().unwrap() {
-            if JobQueue::get_job(&q, *id).is_some() {
+            if JobQueue::get_job(&queue, *id).is_some() {
                 count.push(id);
             }
         }
@@ -173,89 +176,219 @@ pub async fn submit_job(
         None
     };
 
-    let q_size = JobQueue::queue_size(&q);
+    // A delay is turned into an absolute time here so it survives restarts like run_at does
+    let mut invalid_run_at = None;
+    let run_at = match (req.run_at, req.delay.as_deref()) {
+        (Some(_), Some(_)) => {
+            invalid_run_at = Some("Only one of run_at and delay can be given.");
+            None
+        },
+        (Some(at), None) => Some(at),
+        (None, Some(delay)) => match parse_duration(delay) {
+            Ok(d) if d < Duration::zero() => {
+                invalid_run_at = Some("Delay can't be negative.");
+                None
+            },
+            Ok(d) if d.num_seconds() > MAX_DELAY_SECS => {
+                invalid_run_at = Some("Delay can't be longer than a year.");
+                None
+            },
+            Ok(d) => match Utc::now().checked_add_signed(d) {
+                Some(at) => Some(at),
+                None => {
+                    invalid_run_at = Some("Delay is too large.");
+                    None
+                }
+            },
+            Err(err) => {
+                invalid_run_at = Some(err);
+                None
+            }
+        },
+        (None, None) => None
+    };
+
+    if run_at.is_some() && is_recurring {
+        invalid_run_at = Some("run_at and delay can't be used with a schedule.");
+    }
+
+    let is_delayed = run_at.is_some_and(|at| at > Utc::now());
+
+    let retry_policy = req.retry_policy.clone().unwrap_or_default();
+
+    let valid_retry_policy = retry_policy.max_attempts >= 1 
+        && retry_policy.multiplier >= 1.0 
+        && (0.0..=1.0).contains(&retry_policy.jitter)
+        && retry_policy.max_delay_secs >= retry_policy.initial_delay_secs
+        && retry_policy.max_delay_secs <= MAX_RETRY_DELAY_SECS;
+
+    let q_size = JobQueue::queue_size(&queue);
     let mut over_max_jobs = false;
     if q_size >= *MAX_QUEUE_SIZE {
         over_max_jobs = true
     }
 
-    let job = Job {
+    let mut job = Job {
         id: Uuid::new_v4(),
         command: req.command.clone(),
         args: req.args.clone(),
-        status: JobStatus::PENDING,
+        status: if is_delayed { JobStatus::SCHEDULED } else { JobStatus::PENDING },
         timestamp: Utc::now(),
         
         retry_count: 0,
-        max_retries: MAX_RETRIES,
+        max_retries: retry_policy.max_attempts.saturating_sub(1),
+        retry_policy,
+        next_attempt_at: None,
 
         priority: req.priority.clone().unwrap_or(Priority::LOW),
 
         schedule,
-        next_run,
+        interval_secs: interval_secs.flatten(),
+        anchor: interval_secs.flatten().map(|_| req.anchor.unwrap_or_else(Utc::now)),
+        jitter_secs: jitter.and_then(Result::ok).map(|d| d.num_seconds() as u64).filter(|secs| *secs > 0),
+        timezone: req.timezone.clone(),
+        misfire_policy: req.misfire_policy.clone().unwrap_or_default(),
+        max_catchup: req.max_catchup.unwrap_or(DEFAULT_MAX_CATCHUP),
+        concurrency_policy: req.concurrency_policy.clone().unwrap_or_default(),
+        not_before: req.not_before,
+        not_after: req.not_after,
+        max_runs: req.max_runs,
+        run_count: 0,
+        calendars: req.calendars.clone(),
+        blackout_policy: req.blackout_policy.clone().unwrap_or_default(),
+        next_run: None,
         is_recurring,
         parent_schedule_id: None,
+        scheduled_for: None,
+
+        depends_on: depend,
 
-        depends_on: depend
+        run_at,
+
+        timeout_secs: req.timeout_secs,
+
+        env: None,
+
+        lease_token: None,
+        lease_expires_at: None
     };
 
+    if is_recurring {
+        job.next_run = schedule::next_occurrence(&job, Utc::now());
+    }
 
     if fail_request {
         HttpResponse::BadRequest().json(ErrorMessage::new(String::from("400"), String::from("Request failed on parsing dependency UUIDs.")))
+    } else if let Some(err) = invalid_schedule_option {
+        HttpResponse::BadRequest().json(ErrorMessage::new(String::from("400"), err))
+    } else if is_recurring && job.next_run.is_none() {
+        HttpResponse::BadRequest().json(ErrorMessage::new(String::from("400"), String::from("The schedule never runs within its bounds.")))
+    } else if let Some(err) = invalid_run_at {
+        HttpResponse::BadRequest().json(ErrorMessage::new(String::from("400"), format!("Invalid run time: {}", err)))
+    } else if !valid_retry_policy {
+        HttpResponse::BadRequest().json(ErrorMessage::new(String::from("400"), String::from("Invalid retry policy. Needs at least 1 attempt, a multiplier of at least 1, jitter between 0 and 1 and a max delay no lower than the initial delay and no longer than a week.")))
+    } else if req.timeout_secs.is_some_and(|secs| secs == 0 || secs > MAX_TIMEOUT_SECS) {
+        HttpResponse::BadRequest().json(ErrorMessage::new(String::from("400"), String::from("Timeout must be between 1 second and a week.")))
     } else if over_max_jobs {
         HttpResponse::BadRequest().json(ErrorMessage::new(String::from("400"), String::from("Max number of jobs in queue reached.")))
     } else {
         if is_recurring {
             log::info!("New scheduled job added. Job info: id: {:?}, cmd: {:?}, args: {:?}", job.id, job.command, job.args);
-            JobQueue::add_scheduled_jobs(&mut q, job.clone());
+            JobQueue::add_scheduled_jobs(&queue, job.clone());
+        } else if is_delayed {
+            log::info!("New delayed job added. Job info: id: {:?}, cmd: {:?}, args: {:?}, run at: {:?}", job.id, job.command, job.args, job.run_at);
+            JobQueue::add_delayed_job(&queue, job.clone());
         } else {
             log::info!("New job added. Job info: id: {:?}, cmd: {:?}, args: {:?}", job.id, job.command, job.args);
-            JobQueue::add_job(&mut q, job.clone());
+            JobQueue::add_job(&queue, job.clone());
         }
         HttpResponse::Ok().json( job )
     }
 }
 
-pub async fn check_schedules(queue: Arc<Mutex<JobQueue>>) {
-    let mut q = queue.lock().await;
+pub async fn cancel_job(
+    path: web::Path<String>,
+    queue: web::Data<Arc<JobQueue>>
+) -> impl Responder {
+    if let Ok(job_id) = Uuid::parse_str(&path.into_inner()) {
+        match JobQueue::cancel_job(&queue, job_id) {
+            Ok(job) => {
+                log::info!("Job ID: {} has been canceled.", job_id);
+                HttpResponse::Ok().json(job)
+            },
+            Err(CancelError::NotFound) => HttpResponse::NotFound().json(ErrorMessage::new(String::from("404"), format!("No job with id: {}", job_id))),
+            Err(CancelError::AlreadyFinished(status)) => HttpResponse::Conflict().json(ErrorMessage::new(String::from("409"), format!("Job has already finished with status: {}", status.to_string())))
+        }
+    } else {
+        HttpResponse::BadRequest().json(ErrorMessage::new(String::from("400"), String::from("Failed to parse UUID or UUID may be invalid.")))
+    }
+}
+
+pub async fn check_schedules(queue: Arc<JobQueue>) -> Option<DateTime<Utc>> {
+    JobQueue::fire_due_schedules(&queue, Utc::now())
+}
+
+pub async fn check_delayed_jobs(queue: Arc<JobQueue>) {
+    JobQueue::promote_delayed_jobs(&queue);
+    JobQueue::start_backfill_runs(&queue);
+}
 
-    JobQueue::check_scheduled_jobs(&mut q);
+pub async fn evict_finished_jobs(queue: Arc<JobQueue>) {
+    let evicted = JobQueue::evict_finished_jobs(&queue);
+
+    if evicted > 0 {
+        log::info!("Evicted {} finished jobs from memory, {} left.", evicted, JobQueue::queue_size(&queue));
+    }
 }
 
 // Results
 
 pub async fn job_results(
-    req: web::Json<JobResult>,
+    req: web::Json<JobResultReport>,
     path: web::Path<String>,
-    queue: web::Data<Arc<Mutex<JobQueue>>>
+    queue: web::Data<Arc<JobQueue>>
 ) -> impl Responder {
-    let mut q = queue.lock().await;
-    
     if let Ok(id) = Uuid::parse_str(&path.into_inner()) {
-        let job = JobQueue::get_job(&mut q, id);
-
         let results = JobResult {
-            exitcode: req.exitcode,
-            stdout: req.stdout.clone(),
-            stderr: req.stderr.clone()
+            exitcode: req.job_result.exitcode,
+            stdout: req.job_result.stdout.clone(),
+            stderr: req.job_result.stderr.clone(),
+            timed_out: req.job_result.timed_out
         };
 
         // Debug
         log::info!("A new result has been submitted Job ID: {}, Results: {:?}", id, &results);
 
-        if results.exitcode != 0 && job.is_some() {
-            let j = job.unwrap();
-            if j.retry_count < j.max_retries {
-                JobQueue::retry_job(&mut q, id);
-                log::error!("Job ID: {} has failed and is being retried.", id);
-            } else {
-                JobQueue::store_results(&mut q, id, results.clone());
-                JobQueue::update_job_status(&mut q, id, JobStatus::FAILED);
+        // Every run of a known job is recorded, even one whose results come too late to change its status
+        if JobQueue::get_job(&queue, id).is_some() {
+            JobQueue::record_attempt(&queue, id, req.worker_id, req.started_at, req.finished_at, results.clone());
+        }
+
+        let Some((j, outcome)) = JobQueue::settle_result(&queue, id, req.lease_token, results.clone()) else {
+            log::warn!("Rejected results for Job ID: {} from worker {}, lease token {} is no longer valid.", id, req.worker_id, req.lease_token);
+            return HttpResponse::Conflict().json(ErrorMessage::new(String::from("409"), String::from("Lease is no longer valid, the job was finished or re-assigned.")));
+        };
+
+        JobQueue::release_worker_job(&queue, id);
+
+        if results.timed_out && outcome != ResultOutcome::Canceled {
+            crate::metrics::JOBS_TIMED_OUT_TOTAL.inc();
+            log::warn!("Job ID: {} timed out after {:?} seconds.", id, j.timeout_secs);
+        }
+
+        match outcome {
+            // Keep the output of the killed process but leave the job as canceled
+            ResultOutcome::Canceled => log::info!("Job ID: {} was canceled, storing results without changing status.", id),
+            ResultOutcome::Retrying => log::error!("Job ID: {} has failed and is being retried.", id),
+            ResultOutcome::NotRetryable => {
+                JobQueue::dead_letter_job(&queue, id, format!("Exit code {} is not retryable", results.exitcode));
+                log::error!("Job ID: {} has failed with exit code {} which its retry policy doesn't retry.", id, results.exitcode);
+            },
+            ResultOutcome::OutOfRetries => {
+                JobQueue::dead_letter_job(&queue, id, format!("Failed after {} attempts", j.retry_count + 1));
                 log::error!("Job ID: {} has failed after max retries.", id);
-            }
-        } else {
-            JobQueue::store_results(&mut q, id, results.clone());
-            JobQueue::update_job_status(&mut q, id, JobStatus::COMPLETED);
+            },
+            ResultOutcome::Completed => {}
         }
 
         HttpResponse::Ok().json(results)
@@ -266,14 +399,12 @@ pub async fn job_results(
 
 pub async fn job_details(
     path: web::Path<String>,
-    queue: web::Data<Arc<Mutex<JobQueue>>>
+    queue: web::Data<Arc<JobQueue>>
 ) -> impl Responder {
-    let mut q = queue.lock().await;
-
     let id = path.into_inner();
 
     if let Ok(job_id) = Uuid::parse_str(&id) {
-        let details = JobQueue::get_job_status(&mut q, job_id);
+        let details = JobQueue::get_job_status(&queue, job_id);
 
         if details.is_some() {
             HttpResponse::Ok().json(details)
@@ -287,15 +418,299 @@ pub async fn job_details(
 
 pub async fn list_jobs(
     req: web::Json<SubmitJobListRequest>,
-    queue: web::Data<Arc<Mutex<JobQueue>>>
+    queue: web::Data<Arc<JobQueue>>
 ) -> impl Responder {
-    let q = queue.lock().await;
-    
-    let response = JobQueue::get_list(&q, req.status_search.clone());
+    let response = JobQueue::get_list(&queue, req.status_search.clone());
 
     if response.is_ok() {
         HttpResponse::Ok().json(GetJobListResponse{ list: Some(response.unwrap())})
     } else {
         HttpResponse::BadRequest().json(ErrorMessage::new(String::from("400"), String::from("There was an error fetching job list.")))
     }
-}
\ No newline at end of file
+}
+
+// Dead Letters
+
+pub async fn list_dead_letters(
+    queue: web::Data<Arc<JobQueue>>
+) -> impl Responder {
+    match JobQueue::get_dead_letters(&queue) {
+        Ok(list) => HttpResponse::Ok().json(GetDeadLetterListResponse { list }),
+        Err(_) => HttpResponse::BadRequest().json(ErrorMessage::new(String::from("400"), String::from("There was an error fetching the dead letter queue.")))
+    }
+}
+
+pub async fn requeue_dead_letter(
+    path: web::Path<String>,
+    queue: web::Data<Arc<JobQueue>>
+) -> impl Responder {
+    if let Ok(job_id) = Uuid::parse_str(&path.into_inner()) {
+        match JobQueue::requeue_dead_letter(&queue, job_id) {
+            Some(job) => {
+                log::info!("Job ID: {} was requeued from the dead letter queue.", job_id);
+                HttpResponse::Ok().json(job)
+            },
+            None => HttpResponse::NotFound().json(ErrorMessage::new(String::from("404"), format!("No dead letter with id: {}", job_id)))
+        }
+    } else {
+        HttpResponse::BadRequest().json(ErrorMessage::new(String::from("400"), String::from("Failed to parse UUID or UUID may be invalid.")))
+    }
+}
+
+pub async fn purge_dead_letter(
+    path: web::Path<String>,
+    queue: web::Data<Arc<JobQueue>>
+) -> impl Responder {
+    if let Ok(job_id) = Uuid::parse_str(&path.into_inner()) {
+        if JobQueue::purge_dead_letter(&queue, job_id) {
+            log::info!("Job ID: {} was purged from the dead letter queue.", job_id);
+            HttpResponse::Ok().json(DeadLetterBulkResponse { ids: vec![job_id] })
+        } else {
+            HttpResponse::NotFound().json(ErrorMessage::new(String::from("404"), format!("No dead letter with id: {}", job_id)))
+        }
+    } else {
+        HttpResponse::BadRequest().json(ErrorMessage::new(String::from("400"), String::from("Failed to parse UUID or UUID may be invalid.")))
+    }
+}
+
+// Bulk versions so on-call can replay or clear everything in one request instead of hitting the rate limit
+
+pub async fn requeue_all_dead_letters(
+    queue: web::Data<Arc<JobQueue>>
+) -> impl Responder {
+    match JobQueue::get_dead_letters(&queue) {
+        Ok(list) => {
+            let ids: Vec<Uuid> = list.iter()
+                .filter_map(|letter| JobQueue::requeue_dead_letter(&queue, letter.job.id).map(|j| j.id))
+                .collect();
+
+            log::info!("Requeued {} jobs from the dead letter queue.", ids.len());
+            HttpResponse::Ok().json(DeadLetterBulkResponse { ids })
+        },
+        Err(_) => HttpResponse::BadRequest().json(ErrorMessage::new(String::from("400"), String::from("There was an error fetching the dead letter queue.")))
+    }
+}
+
+pub async fn purge_all_dead_letters(
+    queue: web::Data<Arc<JobQueue>>
+) -> impl Responder {
+    match JobQueue::get_dead_letters(&queue) {
+        Ok(list) => {
+            let ids: Vec<Uuid> = list.iter()
+                .map(|letter| letter.job.id)
+                .filter(|id| JobQueue::purge_dead_letter(&queue, *id))
+                .collect();
+
+            log::info!("Purged {} jobs from the dead letter queue.", ids.len());
+            HttpResponse::Ok().json(DeadLetterBulkResponse { ids })
+        },
+        Err(_) => HttpResponse::BadRequest().json(ErrorMessage::new(String::from("400"), String::from("There was an error fetching the dead letter queue.")))
+    }
+}
+
+// Schedules
+
+pub async fn update_schedule(
+    path: web::Path<String>,
+    req: web::Json<UpdateScheduleRequest>,
+    queue: web::Data<Arc<JobQueue>>
+) -> impl Responder {
+    if let Ok(job_id) = Uuid::parse_str(&path.into_inner()) {
+        match JobQueue::update_schedule(&queue, job_id, req.into_inner()) {
+            Ok(job) => {
+                log::info!("Schedule ID: {} has been updated.", job_id);
+                HttpResponse::Ok().json(job)
+            },
+            Err(err) => schedule_error(job_id, err)
+        }
+    } else {
+        HttpResponse::BadRequest().json(ErrorMessage::new(String::from("400"), String::from("Failed to parse UUID or UUID may be invalid.")))
+    }
+}
+
+pub async fn pause_schedule(
+    path: web::Path<String>,
+    queue: web::Data<Arc<JobQueue>>
+) -> impl Responder {
+    if let Ok(job_id) = Uuid::parse_str(&path.into_inner()) {
+        match JobQueue::pause_schedule(&queue, job_id) {
+            Ok(job) => {
+                log::info!("Schedule ID: {} has been paused.", job_id);
+                HttpResponse::Ok().json(job)
+            },
+            Err(err) => schedule_error(job_id, err)
+        }
+    } else {
+        HttpResponse::BadRequest().json(ErrorMessage::new(String::from("400"), String::from("Failed to parse UUID or UUID may be invalid.")))
+    }
+}
+
+pub async fn resume_schedule(
+    path: web::Path<String>,
+    queue: web::Data<Arc<JobQueue>>
+) -> impl Responder {
+    if let Ok(job_id) = Uuid::parse_str(&path.into_inner()) {
+        match JobQueue::resume_schedule(&queue, job_id) {
+            Ok(job) => {
+                log::info!("Schedule ID: {} has been resumed.", job_id);
+                HttpResponse::Ok().json(job)
+            },
+            Err(err) => schedule_error(job_id, err)
+        }
+    } else {
+        HttpResponse::BadRequest().json(ErrorMessage::new(String::from("400"), String::from("Failed to parse UUID or UUID may be invalid.")))
+    }
+}
+
+pub async fn delete_schedule(
+    path: web::Path<String>,
+    queue: web::Data<Arc<JobQueue>>
+) -> impl Responder {
+    if let Ok(job_id) = Uuid::parse_str(&path.into_inner()) {
+        match JobQueue::delete_schedule(&queue, job_id) {
+            Ok(job) => {
+                log::info!("Schedule ID: {} has been deleted.", job_id);
+                HttpResponse::Ok().json(job)
+            },
+            Err(err) => schedule_error(job_id, err)
+        }
+    } else {
+        HttpResponse::BadRequest().json(ErrorMessage::new(String::from("400"), String::from("Failed to parse UUID or UUID may be invalid.")))
+    }
+}
+
+pub async fn schedule_runs(
+    path: web::Path<String>,
+    query: web::Query<ScheduleRunsQuery>,
+    queue: web::Data<Arc<JobQueue>>
+) -> impl Responder {
+    if let Ok(job_id) = Uuid::parse_str(&path.into_inner()) {
+        let limit = query.limit.unwrap_or(DEFAULT_RUNS_LIMIT).min(MAX_SCHEDULE_LIST);
+
+        match JobQueue::get_schedule_runs(&queue, job_id, limit) {
+            Some(runs) => HttpResponse::Ok().json(GetScheduleRunsResponse { schedule_id: job_id, runs }),
+            None => schedule_error(job_id, ScheduleError::NotFound)
+        }
+    } else {
+        HttpResponse::BadRequest().json(ErrorMessage::new(String::from("400"), String::from("Failed to parse UUID or UUID may be invalid.")))
+    }
+}
+
+pub async fn schedule_preview(
+    path: web::Path<String>,
+    query: web::Query<SchedulePreviewQuery>,
+    queue: web::Data<Arc<JobQueue>>
+) -> impl Responder {
+    if let Ok(job_id) = Uuid::parse_str(&path.into_inner()) {
+        let n = query.n.unwrap_or(DEFAULT_PREVIEW_COUNT).min(MAX_SCHEDULE_LIST);
+
+        match JobQueue::preview_schedule(&queue, job_id, n) {
+            Some(next_runs) => HttpResponse::Ok().json(GetSchedulePreviewResponse { 
+                schedule_id: job_id, 
+                timezone: JobQueue::get_job(&queue, job_id).and_then(|j| j.timezone), 
+                next_runs 
+            }),
+            None => schedule_error(job_id, ScheduleError::NotFound)
+        }
+    } else {
+        HttpResponse::BadRequest().json(ErrorMessage::new(String::from("400"), String::from("Failed to parse UUID or UUID may be invalid.")))
+    }
+}
+
+pub async fn backfill_schedule(
+    path: web::Path<String>,
+    req: web::Json<BackfillRequest>,
+    queue: web::Data<Arc<JobQueue>>
+) -> impl Responder {
+    let Ok(job_id) = Uuid::parse_str(&path.into_inner()) else {
+        return HttpResponse::BadRequest().json(ErrorMessage::new(String::from("400"), String::from("Failed to parse UUID or UUID may be invalid.")));
+    };
+
+    let max_in_flight = req.max_in_flight.unwrap_or(DEFAULT_BACKFILL_IN_FLIGHT);
+
+    if req.from >= req.to {
+        HttpResponse::BadRequest().json(ErrorMessage::new(String::from("400"), String::from("from must be earlier than to.")))
+    } else if req.to > Utc::now() {
+        HttpResponse::BadRequest().json(ErrorMessage::new(String::from("400"), String::from("A backfill can only cover the past, to can't be in the future.")))
+    } else if max_in_flight == 0 {
+        HttpResponse::BadRequest().json(ErrorMessage::new(String::from("400"), String::from("max_in_flight must be at least 1.")))
+    } else {
+        match JobQueue::backfill_schedule(&queue, job_id, req.from, req.to, max_in_flight) {
+            Ok(runs) if runs.is_empty() => HttpResponse::BadRequest().json(ErrorMessage::new(String::from("400"), String::from("The schedule doesn't fire between from and to."))),
+            Ok(runs) => HttpResponse::Ok().json(BackfillResponse { schedule_id: job_id, runs, max_in_flight }),
+            Err(err) => schedule_error(job_id, err)
+        }
+    }
+}
+
+fn schedule_error(job_id: Uuid, err: ScheduleError) -> HttpResponse {
+    match err {
+        ScheduleError::NotFound => HttpResponse::NotFound().json(ErrorMessage::new(String::from("404"), format!("No schedule with id: {}", job_id))),
+        ScheduleError::InvalidSchedule => HttpResponse::BadRequest().json(ErrorMessage::new(String::from("400"), String::from("Invalid schedule. Give either a cron or an interval between 1 second and a year."))),
+        ScheduleError::Finished => HttpResponse::Conflict().json(ErrorMessage::new(String::from("409"), format!("Schedule with id: {} has already completed", job_id))),
+        ScheduleError::TooManyRuns => HttpResponse::BadRequest().json(ErrorMessage::new(String::from("400"), format!("A backfill can queue at most {} runs, narrow the range.", MAX_BACKFILL_RUNS)))
+    }
+}
+
+// Calendars
+
+pub async fn put_calendar(
+    path: web::Path<String>,
+    req: web::Json<PutCalendarRequest>,
+    queue: web::Data<Arc<JobQueue>>
+) -> impl Responder {
+    let name = path.into_inner();
+
+    let windows: Result<Vec<BlackoutWindow>, String> = req.into_inner().windows.into_iter()
+        .map(schedule::normalize_window)
+        .collect();
+
+    match windows {
+        Ok(windows) => {
+            let calendar = Calendar { name: name.clone(), windows };
+
+            log::info!("Calendar {} saved with {} blackout windows.", name, calendar.windows.len());
+            JobQueue::put_calendar(&queue, calendar.clone());
+
+            HttpResponse::Ok().json(calendar)
+        },
+        Err(err) => HttpResponse::BadRequest().json(ErrorMessage::new(String::from("400"), err))
+    }
+}
+
+pub async fn get_calendar(
+    path: web::Path<String>,
+    queue: web::Data<Arc<JobQueue>>
+) -> impl Responder {
+    let name = path.into_inner();
+
+    match JobQueue::get_calendar(&queue, &name) {
+        Some(calendar) => HttpResponse::Ok().json(calendar),
+        None => HttpResponse::NotFound().json(ErrorMessage::new(String::from("404"), format!("No calendar named: {}", name)))
+    }
+}
+
+pub async fn list_calendars(
+    queue: web::Data<Arc<JobQueue>>
+) -> impl Responder {
+    HttpResponse::Ok().json(GetCalendarListResponse { list: JobQueue::get_calendars(&queue) })
+}
+
+pub async fn delete_calendar(
+    path: web::Path<String>,
+    queue: web::Data<Arc<JobQueue>>
+) -> impl Responder {
+    let name = path.into_inner();
+
+    match JobQueue::delete_calendar(&queue, &name) {
+        Ok(calendar) => {
+            log::info!("Calendar {} has been deleted.", name);
+            HttpResponse::Ok().json(calendar)
+        },
+        Err(CalendarError::NotFound) => HttpResponse::NotFound().json(ErrorMessage::new(String::from("404"), format!("No calendar named: {}", name))),
+        Err(CalendarError::InUse(ids)) => {
+            let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+            HttpResponse::Conflict().json(ErrorMessage::new(String::from("409"), format!("Calendar {} is used by schedules: {}", name, ids.join(", "))))
+        }
+    }
+}
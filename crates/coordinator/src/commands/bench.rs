@@ -0,0 +1,157 @@
+use common::job::{JobResult, JobStatus, Priority, Job};
+use rusqlite::{Connection, Error};
+use std::{path::Path, process::exit, time::{Duration, Instant}};
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::{db, store::{JobStore, StoreError, sqlite::{SqliteStore, remove_db, temp_path}}};
+
+// submit, claim (status + lease), result, attempt, finish
+const WRITES_PER_JOB: usize = 6;
+
+struct Run {
+    total: Duration,
+    // Time spent inside the store calls, what the queue lock would have been held for
+    waited: Duration
+}
+
+// Writes `jobs` jobs through their whole life the way the queue does, once straight to SQLite like the
+// coordinator used to and once through the writer thread, each into a fresh database in the temp dir
+pub fn run(jobs: usize) {
+    println!("Writing {} jobs, {} writes each (submit, claim, lease, result, attempt, finish)\n", jobs, WRITES_PER_JOB);
+
+    let direct_path = temp_path("bench-direct");
+    let direct = bench_direct(&direct_path, jobs).unwrap_or_else(|e| {
+        println!("Direct run failed: {}", e);
+        exit(1);
+    });
+    remove_db(&direct_path);
+
+    let writer_path = temp_path("bench-writer");
+    let writer = bench_writer(&writer_path, jobs).unwrap_or_else(|e| {
+        println!("Writer thread run failed: {}", e);
+        exit(1);
+    });
+    remove_db(&writer_path);
+
+    println!("{:<16} {:>10} {:>10} {:>20}", "", "total", "jobs/s", "avg wait per write");
+    print_run("direct", &direct, jobs);
+    print_run("writer thread", &writer, jobs);
+
+    println!("\n{:.1}x the throughput, {:.0}x less time waiting per write",
+        direct.total.as_secs_f64() / writer.total.as_secs_f64(),
+        direct.waited.as_secs_f64() / writer.waited.as_secs_f64().max(f64::EPSILON));
+}
+
+fn print_run(name: &str, run: &Run, jobs: usize) {
+    println!("{:<16} {:>9.3}s {:>10.0} {:>18.1}µs",
+        name,
+        run.total.as_secs_f64(),
+        jobs as f64 / run.total.as_secs_f64(),
+        run.waited.as_secs_f64() * 1e6 / (jobs * WRITES_PER_JOB) as f64);
+}
+
+// One transaction per statement on the default rollback journal
+fn bench_direct(path: &Path, jobs: usize) -> Result<Run, Error> {
+    let mut conn = Connection::open(path)?;
+    db::init(&mut conn)?;
+
+    let worker_id = Uuid::new_v4();
+    let start = Instant::now();
+
+    for token in 0..jobs as u64 {
+        let job = bench_job();
+        let id = job.id;
+
+        db::insert_job(&conn, job)?;
+        db::update_job_status(&conn, id, JobStatus::RUNNING)?;
+        db::update_job_lease(&conn, id, token, Utc::now())?;
+        db::insert_results(&conn, id, bench_result())?;
+        db::insert_attempt(&conn, id, worker_id, Utc::now(), Utc::now(), bench_result())?;
+        db::update_job_status(&conn, id, JobStatus::COMPLETED)?;
+    }
+
+    let total = start.elapsed();
+
+    Ok(Run { total, waited: total })
+}
+
+fn bench_writer(path: &Path, jobs: usize) -> Result<Run, StoreError> {
+    let store = SqliteStore::open(&path.to_string_lossy())?;
+
+    let worker_id = Uuid::new_v4();
+    let start = Instant::now();
+
+    for token in 0..jobs as u64 {
+        let job = bench_job();
+        let id = job.id;
+
+        store.insert_job(job)?;
+        store.update_job_status(id, JobStatus::RUNNING)?;
+        store.update_job_lease(id, token, Utc::now())?;
+        store.insert_results(id, bench_result())?;
+        store.insert_attempt(id, worker_id, Utc::now(), Utc::now(), bench_result())?;
+        store.update_job_status(id, JobStatus::COMPLETED)?;
+    }
+
+    let waited = start.elapsed();
+    store.flush()?;
+
+    Ok(Run { total: start.elapsed(), waited })
+}
+
+fn bench_result() -> JobResult {
+    JobResult {
+        exitcode: 0,
+        stdout: String::from("done\n"),
+        stderr: String::new(),
+        timed_out: false
+    }
+}
+
+fn bench_job() -> Job {
+    Job {
+        id: Uuid::new_v4(),
+        command: String::from("echo"),
+        args: vec![String::from("bench")],
+        status: JobStatus::PENDING,
+        timestamp: Utc::now(),
+
+        retry_count: 0,
+        max_retries: 3,
+        retry_policy: Default::default(),
+        next_attempt_at: None,
+
+        priority: Priority::MEDIUM,
+
+        schedule: None,
+        interval_secs: None,
+        anchor: None,
+        jitter_secs: None,
+        timezone: None,
+        misfire_policy: Default::default(),
+        max_catchup: 0,
+        concurrency_policy: Default::default(),
+        not_before: None,
+        not_after: None,
+        max_runs: None,
+        run_count: 0,
+        calendars: None,
+        blackout_policy: Default::default(),
+        next_run: None,
+        is_recurring: false,
+        parent_schedule_id: None,
+        scheduled_for: None,
+
+        depends_on: None,
+
+        run_at: None,
+
+        timeout_secs: None,
+
+        env: None,
+
+        lease_token: None,
+        lease_expires_at: None
+    }
+}
@@ -0,0 +1,293 @@
+use common::{
+    job::{JobResult, JobStatus, Priority, Job},
+    message::{JobLease, WorkerHeartbeat, WorkerInfo, WorkerStatus}
+};
+use std::{
+    collections::HashSet,
+    process::exit,
+    sync::{Arc, Mutex, atomic::{AtomicUsize, Ordering}},
+    thread,
+    time::{Duration, Instant}
+};
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::{queue::JobQueue, store::{JobStore, memory::MemoryStore, sqlite::{SqliteStore, remove_db, temp_path}}};
+
+// How often each simulated worker heartbeats, a real one does every few seconds
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(20);
+// How long a worker waits before polling again after finding the queue empty
+const POLL_INTERVAL: Duration = Duration::from_millis(2);
+// How long every job "runs" for
+const JOB_TIME: Duration = Duration::from_millis(5);
+// Pause between one reader's lookups, like a client polling a job's status
+const READ_INTERVAL: Duration = Duration::from_millis(1);
+const CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+// Every call made while holding it, the way the handlers shared one Mutex<JobQueue>
+type GlobalLock = Option<Arc<Mutex<()>>>;
+
+#[derive(Default)]
+struct Latencies {
+    heartbeat: Vec<Duration>,
+    next_job: Vec<Duration>,
+    result: Vec<Duration>,
+    lookup: Vec<Duration>,
+    check: Vec<Duration>
+}
+
+impl Latencies {
+    fn merge(&mut self, other: Latencies) {
+        self.heartbeat.extend(other.heartbeat);
+        self.next_job.extend(other.next_job);
+        self.result.extend(other.result);
+        self.lookup.extend(other.lookup);
+        self.check.extend(other.check);
+    }
+}
+
+struct Run {
+    total: Duration,
+    latencies: Latencies
+}
+
+// Drives the queue in-process with a thread per simulated worker, API client and the dead worker check,
+// once with every call behind one lock and once on the queue's own locks. The in-memory store keeps
+// the disk out of it so only the locking is measured, `sqlite` puts a temporary database behind it instead
+pub fn run(workers: usize, jobs: usize, readers: usize, sqlite: bool) {
+    println!("{} workers, {} readers looking up jobs, {} jobs running {}ms each ({} store)\n",
+        workers, readers, jobs, JOB_TIME.as_millis(), if sqlite { "sqlite" } else { "memory" });
+
+    let one_lock = simulate(workers, jobs, readers, sqlite, Some(Arc::new(Mutex::new(()))));
+    let split = simulate(workers, jobs, readers, sqlite, None);
+
+    println!("{:<12} {:>9} {:>8} {:>20} {:>20} {:>20} {:>20} {:>20}", "", "total", "jobs/s",
+        "heartbeat p50/p99", "next job p50/p99", "result p50/p99", "lookup p50/p99", "worker check p50/p99");
+    print_run("one lock", &one_lock, jobs);
+    print_run("split locks", &split, jobs);
+}
+
+fn print_run(name: &str, run: &Run, jobs: usize) {
+    println!("{:<12} {:>8.2}s {:>8.0} {:>20} {:>20} {:>20} {:>20} {:>20}",
+        name,
+        run.total.as_secs_f64(),
+        jobs as f64 / run.total.as_secs_f64(),
+        percentiles(&run.latencies.heartbeat),
+        percentiles(&run.latencies.next_job),
+        percentiles(&run.latencies.result),
+        percentiles(&run.latencies.lookup),
+        percentiles(&run.latencies.check));
+}
+
+fn percentiles(samples: &[Duration]) -> String {
+    if samples.is_empty() {
+        return String::from("-");
+    }
+
+    let mut sorted = samples.to_vec();
+    sorted.sort();
+
+    let at = |p: f64| sorted[((sorted.len() - 1) as f64 * p) as usize].as_secs_f64() * 1e6;
+
+    format!("{:.0}/{:.0}µs", at(0.5), at(0.99))
+}
+
+// Times the call, including the wait for the global lock when there is one
+fn call<T>(global: &GlobalLock, samples: &mut Vec<Duration>, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let _guard = global.as_ref().map(|lock| lock.lock().unwrap());
+    let value = f();
+    samples.push(start.elapsed());
+
+    value
+}
+
+fn simulate(workers: usize, jobs: usize, readers: usize, sqlite: bool, global: GlobalLock) -> Run {
+    let path = temp_path("loadtest");
+    let store: Box<dyn JobStore> = if sqlite {
+        Box::new(SqliteStore::open(&path.to_string_lossy()).unwrap_or_else(|e| {
+            println!("Failed to open {}: {}", path.to_string_lossy(), e);
+            exit(1);
+        }))
+    } else {
+        Box::new(MemoryStore::new())
+    };
+
+    let queue = Arc::new(JobQueue::new(store));
+    let finished = Arc::new(AtomicUsize::new(0));
+
+    let worker_ids: Vec<Uuid> = (0..workers).map(|_| Uuid::new_v4()).collect();
+    for worker_id in &worker_ids {
+        queue.register_worker(WorkerInfo {
+            worker_id: *worker_id,
+            hostname: String::from("loadtest"),
+            last_seen: Utc::now(),
+            status: WorkerStatus::ALIVE,
+            current_job_ids: HashSet::new(),
+            capacity: 1
+        });
+    }
+
+    let batch: Vec<Job> = (0..jobs).map(|_| loadtest_job()).collect();
+    let job_ids: Arc<Vec<Uuid>> = Arc::new(batch.iter().map(|job| job.id).collect());
+
+    let start = Instant::now();
+    let mut threads = vec![];
+
+    {
+        let (queue, global) = (queue.clone(), global.clone());
+
+        threads.push(thread::spawn(move || {
+            for job in batch {
+                let _guard = global.as_ref().map(|lock| lock.lock().unwrap());
+                queue.add_job(job);
+            }
+
+            Latencies::default()
+        }));
+    }
+
+    for worker_id in worker_ids {
+        let (queue, global, finished) = (queue.clone(), global.clone(), finished.clone());
+
+        threads.push(thread::spawn(move || simulate_worker(&queue, &global, &finished, jobs, worker_id)));
+    }
+
+    for reader in 0..readers {
+        let (queue, global, finished, job_ids) = (queue.clone(), global.clone(), finished.clone(), job_ids.clone());
+
+        threads.push(thread::spawn(move || {
+            let mut latencies = Latencies::default();
+            let mut next = reader;
+
+            while finished.load(Ordering::Relaxed) < jobs {
+                let job_id = job_ids[next % job_ids.len()];
+                next += readers;
+
+                call(&global, &mut latencies.lookup, || queue.get_job_status(job_id));
+                thread::sleep(READ_INTERVAL);
+            }
+
+            latencies
+        }));
+    }
+
+    {
+        let (queue, global, finished) = (queue.clone(), global.clone(), finished.clone());
+
+        threads.push(thread::spawn(move || {
+            let mut latencies = Latencies::default();
+
+            while finished.load(Ordering::Relaxed) < jobs {
+                call(&global, &mut latencies.check, || queue.check_worker());
+                thread::sleep(CHECK_INTERVAL);
+            }
+
+            latencies
+        }));
+    }
+
+    let mut latencies = Latencies::default();
+    for handle in threads {
+        latencies.merge(handle.join().expect("Load test thread panicked"));
+    }
+
+    let total = start.elapsed();
+
+    drop(queue);
+    remove_db(&path);
+
+    Run { total, latencies }
+}
+
+fn simulate_worker(queue: &JobQueue, global: &GlobalLock, finished: &AtomicUsize, jobs: usize, worker_id: Uuid) -> Latencies {
+    let mut latencies = Latencies::default();
+    let mut running: Option<(Job, Instant)> = None;
+    let mut last_beat = Instant::now();
+
+    while finished.load(Ordering::Relaxed) < jobs {
+        if last_beat.elapsed() >= HEARTBEAT_INTERVAL {
+            let heartbeat = WorkerHeartbeat {
+                worker_id,
+                timestamp: Utc::now(),
+                leases: running.iter().map(|(job, _)| JobLease { job_id: job.id, lease_token: job.lease_token.unwrap_or_default() }).collect()
+            };
+
+            call(global, &mut latencies.heartbeat, || queue.update_worker_heartbeat(heartbeat));
+            last_beat = Instant::now();
+        }
+
+        match running.take() {
+            Some((job, started)) if started.elapsed() >= JOB_TIME => {
+                let result = JobResult { exitcode: 0, stdout: String::from("done\n"), stderr: String::new(), timed_out: false };
+
+                // What the results handler does
+                call(global, &mut latencies.result, || {
+                    if queue.settle_result(job.id, job.lease_token.unwrap_or_default(), result.clone()).is_some() {
+                        queue.release_worker_job(job.id);
+                        queue.record_attempt(job.id, worker_id, Utc::now(), Utc::now(), result);
+                    }
+                });
+
+                finished.fetch_add(1, Ordering::Relaxed);
+            },
+            Some(still_running) => {
+                running = Some(still_running);
+                thread::sleep(Duration::from_millis(1));
+            },
+            None => match call(global, &mut latencies.next_job, || queue.get_next_job(worker_id)) {
+                Some(job) => running = Some((job, Instant::now())),
+                None => thread::sleep(POLL_INTERVAL)
+            }
+        }
+    }
+
+    latencies
+}
+
+fn loadtest_job() -> Job {
+    Job {
+        id: Uuid::new_v4(),
+        command: String::from("echo"),
+        args: vec![String::from("loadtest")],
+        status: JobStatus::PENDING,
+        timestamp: Utc::now(),
+
+        retry_count: 0,
+        max_retries: 3,
+        retry_policy: Default::default(),
+        next_attempt_at: None,
+
+        priority: Priority::MEDIUM,
+
+        schedule: None,
+        interval_secs: None,
+        anchor: None,
+        jitter_secs: None,
+        timezone: None,
+        misfire_policy: Default::default(),
+        max_catchup: 0,
+        concurrency_policy: Default::default(),
+        not_before: None,
+        not_after: None,
+        max_runs: None,
+        run_count: 0,
+        calendars: None,
+        blackout_policy: Default::default(),
+        next_run: None,
+        is_recurring: false,
+        parent_schedule_id: None,
+        scheduled_for: None,
+
+        depends_on: None,
+
+        run_at: None,
+
+        timeout_secs: None,
+
+        env: None,
+
+        lease_token: None,
+        lease_expires_at: None
+    }
+}
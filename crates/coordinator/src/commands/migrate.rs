@@ -0,0 +1,75 @@
+use rusqlite::Connection;
+use std::process::exit;
+
+use crate::{db::migrations, store::{self, Backend}};
+
+// Only SQLite has versioned migrations, the Postgres store creates its schema when it connects
+pub fn run(status: bool, dry_run: bool) {
+    match store::backend() {
+        Ok(Backend::Sqlite) => {},
+        Ok(backend) => {
+            println!("The {} store has no migrations, they only apply to SQLite.", backend);
+            exit(1);
+        },
+        Err(err) => {
+            println!("{}", err);
+            exit(1);
+        }
+    }
+
+    let path = store::sqlite_path();
+    let mut conn = Connection::open(&path).unwrap_or_else(|e| {
+        println!("Failed to open {}: {}", path, e);
+        exit(1);
+    });
+
+    if status {
+        print_status(&conn);
+    } else if dry_run {
+        match migrations::dry_run(&mut conn) {
+            Ok(pending) if pending.is_empty() => println!("Database is up to date, nothing to migrate."),
+            Ok(pending) => {
+                println!("{} migration(s) would be applied, all of them apply cleanly:", pending.len());
+
+                for migration in pending {
+                    println!("\t{:>3}  {}", migration.version, migration.name);
+                }
+            },
+            Err((migration, err)) => {
+                println!("Migration {} ({}) fails: {}", migration.version, migration.name, err);
+                exit(1);
+            }
+        }
+    } else {
+        match migrations::migrate(&mut conn) {
+            Ok(applied) if applied.is_empty() => println!("Database is up to date, nothing to migrate."),
+            Ok(applied) => {
+                for migration in &applied {
+                    println!("Applied {:>3}  {}", migration.version, migration.name);
+                }
+
+                println!("Database is at version {}", migrations::current_version(&conn).unwrap_or_default());
+            },
+            Err(err) => {
+                println!("Migration failed, the failing migration was rolled back: {}", err);
+                exit(1);
+            }
+        }
+    }
+}
+
+fn print_status(conn: &Connection) {
+    let (Ok(version), Ok(applied)) = (migrations::current_version(conn), migrations::applied(conn)) else {
+        println!("Failed to read the schema version");
+        exit(1);
+    };
+
+    println!("Schema version: {} of {}\n", version, migrations::MIGRATIONS.last().map_or(0, |m| m.version));
+
+    for migration in migrations::MIGRATIONS {
+        match applied.iter().find(|a| a.version == migration.version) {
+            Some(a) => println!("\t{:>3}  {:<32} applied {}", a.version, a.name, a.applied_at),
+            None => println!("\t{:>3}  {:<32} pending", migration.version, migration.name)
+        }
+    }
+}
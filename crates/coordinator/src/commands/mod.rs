@@ -0,0 +1,3 @@
+pub mod bench;
+pub mod loadtest;
+pub mod migrate;
@@ -1,59 +1,42 @@
-use common::job::{
+use common::{
+    calendar::{BlackoutPolicy, BlackoutWindow, Calendar},
+    job::{
+        ConcurrencyPolicy,
+        JobAttempt,
         JobResult, 
         JobStatus, 
+        MisfirePolicy,
         Priority,
+        RetryPolicy,
         Job,
+    },
+    message::{DeadLetter, ScheduleRun}
 };
 use rusqlite::{
-    Connection, Error, params, types::Type
+    Connection, Error, OptionalExtension, Row, params, types::Type
 };
-use std::str::FromStr;
+use std::{collections::HashMap, str::FromStr};
 use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
-pub fn init(conn: &Connection) -> Result<(), Error> {
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS jobs (
-            id UUID PRIMARY Key,
-            command TEXT,
-            args TEXT,
-            status TEXT,
-            timestamp TIMESTAMP,
-            
-            retry_count INTEGER,
-            max_retries INTEGER,
-            
-            priority TEXT,
-
-            schedule TEXT,
-            next_run TIMESTAMP,
-            is_recurring BOOL,
-            parent_schedule_id UUID,
-
-            depends_on TEXT
-        );",
-        ()
-    )?;
+pub mod migrations;
 
-    conn.execute(
-            "CREATE TABLE IF NOT EXISTS results (
-            id UUID,
-            exitcode INTEGER,
-            stdout TEXT,
-            stderr TEXT,
-            FOREIGN KEY(id) REFERENCES jobs(id)
-        );",
-        ()
-    )?;
+// The schema is owned by `migrations`, anything missing from an older database file is added there
+pub fn init(conn: &mut Connection) -> Result<(), Error> {
+    let applied = migrations::migrate(conn)?;
+
+    if !applied.is_empty() {
+        log::info!("Database migrated to version {}", migrations::current_version(conn)?);
+    }
 
     Ok(())
 }
 
 pub fn insert_job(conn: &Connection, job: Job) -> Result<(), Error> {
     conn.execute(
-        "INSERT INTO jobs (id, command, args, status, timestamp, retry_count, max_retries, priority, schedule, next_run, is_recurring, parent_schedule_id, depends_on) 
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)", 
-        (
+        "INSERT INTO jobs (id, command, args, status, timestamp, retry_count, max_retries, priority, schedule, next_run, is_recurring, parent_schedule_id, depends_on, timeout_secs, lease_token, lease_expires_at, retry_policy, next_attempt_at, run_at, timezone, misfire_policy, max_catchup, concurrency_policy, not_before, not_after, max_runs, run_count, calendars, blackout_policy, interval_secs, anchor, jitter_secs, env, scheduled_for) 
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29, ?30, ?31, ?32, ?33, ?34)", 
+        params![
             job.id.to_string(), 
             job.command, 
             serde_json::to_string(&job.args).map_err(|err| Error::ToSqlConversionFailure(Box::new(err)))?, 
@@ -70,8 +53,38 @@ pub fn insert_job(conn: &Connection, job: Job) -> Result<(), Error> {
             job.is_recurring,
             job.parent_schedule_id.map(|id| id.to_string()),
 
-            serde_json::to_string(&job.depends_on).map_err(|err| Error::ToSqlConversionFailure(Box::new(err)))?
-        ),
+            serde_json::to_string(&job.depends_on).map_err(|err| Error::ToSqlConversionFailure(Box::new(err)))?,
+
+            job.timeout_secs,
+
+            job.lease_token,
+            job.lease_expires_at.map(|t| t.to_rfc3339()),
+
+            serde_json::to_string(&job.retry_policy).map_err(|err| Error::ToSqlConversionFailure(Box::new(err)))?,
+            job.next_attempt_at.map(|t| t.to_rfc3339()),
+
+            job.run_at.map(|t| t.to_rfc3339()),
+
+            job.timezone,
+            job.misfire_policy.to_string(),
+            job.max_catchup,
+            job.concurrency_policy.to_string(),
+
+            job.not_before.map(|t| t.to_rfc3339()),
+            job.not_after.map(|t| t.to_rfc3339()),
+            job.max_runs,
+            job.run_count,
+
+            serde_json::to_string(&job.calendars).map_err(|err| Error::ToSqlConversionFailure(Box::new(err)))?,
+            job.blackout_policy.to_string(),
+
+            job.interval_secs,
+            job.anchor.map(|t| t.to_rfc3339()),
+            job.jitter_secs,
+
+            serde_json::to_string(&job.env).map_err(|err| Error::ToSqlConversionFailure(Box::new(err)))?,
+            job.scheduled_for.map(|t| t.to_rfc3339())
+        ],
     )?;
 
     Ok(())
@@ -79,13 +92,27 @@ pub fn insert_job(conn: &Connection, job: Job) -> Result<(), Error> {
 
 pub fn insert_results(conn: &Connection, job_id: Uuid, results: JobResult) -> Result<(), Error> {
     conn.execute(
-        "INSERT INTO results (id, exitcode, stdout, stderr) VALUES (?1, ?2, ?3, ?4)", 
-        (job_id.to_string(), results.exitcode, results.stdout, results.stderr),
+        "INSERT INTO results (id, exitcode, stdout, stderr, timed_out) VALUES (?1, ?2, ?3, ?4, ?5)", 
+        (job_id.to_string(), results.exitcode, results.stdout, results.stderr, results.timed_out),
     )?;
 
     Ok(())
 }
 
+// Latest result, a requeued job can have one per finished run
+pub fn get_result(conn: &Connection, job_id: Uuid) -> Result<Option<JobResult>, Error> {
+    conn.query_row(
+        "SELECT exitcode, stdout, stderr, timed_out FROM results WHERE id = ?1 ORDER BY rowid DESC LIMIT 1", 
+        params![job_id.to_string()], 
+        |row| Ok(JobResult {
+            exitcode: row.get(0)?,
+            stdout: row.get(1)?,
+            stderr: row.get(2)?,
+            timed_out: row.get(3)?
+        })
+    ).optional()
+}
+
 pub fn update_schedule_run(conn: &Connection, id: Uuid, next_run: DateTime<Utc>) -> Result<(), Error> {
     conn.execute(
         "UPDATE jobs SET next_run = ?1 WHERE id = ?2", 
@@ -95,6 +122,33 @@ pub fn update_schedule_run(conn: &Connection, id: Uuid, next_run: DateTime<Utc>)
     Ok(())
 }
 
+pub fn update_schedule(conn: &Connection, job: &Job) -> Result<(), Error> {
+    conn.execute(
+        "UPDATE jobs SET command = ?1, args = ?2, priority = ?3, schedule = ?4, interval_secs = ?5, anchor = ?6, next_run = ?7 WHERE id = ?8", 
+        (
+            &job.command,
+            serde_json::to_string(&job.args).map_err(|err| Error::ToSqlConversionFailure(Box::new(err)))?,
+            job.priority.to_string(),
+            &job.schedule,
+            job.interval_secs,
+            job.anchor.map(|t| t.to_rfc3339()),
+            job.next_run.map(|t| t.to_rfc3339()),
+            job.id.to_string()
+        )
+    )?;
+
+    Ok(())
+}
+
+pub fn update_run_count(conn: &Connection, id: Uuid, run_count: u32) -> Result<(), Error> {
+    conn.execute(
+        "UPDATE jobs SET run_count = ?1 WHERE id = ?2", 
+        (run_count, id.to_string())
+    )?;
+
+    Ok(())
+}
+
 pub fn update_job_status(conn: &Connection, job_id: Uuid, status: JobStatus) -> Result<(), Error> {
     conn.execute(
         "UPDATE jobs SET status = ?1 WHERE id = ?2",
@@ -104,6 +158,33 @@ pub fn update_job_status(conn: &Connection, job_id: Uuid, status: JobStatus) ->
     Ok(())
 }
 
+pub fn update_next_attempt(conn: &Connection, job_id: Uuid, next_attempt_at: DateTime<Utc>) -> Result<(), Error> {
+    conn.execute(
+        "UPDATE jobs SET next_attempt_at = ?1 WHERE id = ?2", 
+        (next_attempt_at.to_rfc3339(), job_id.to_string()),
+    )?;
+
+    Ok(())
+}
+
+pub fn update_job_lease(conn: &Connection, job_id: Uuid, token: u64, expires_at: DateTime<Utc>) -> Result<(), Error> {
+    conn.execute(
+        "UPDATE jobs SET lease_token = ?1, lease_expires_at = ?2 WHERE id = ?3", 
+        (token, expires_at.to_rfc3339(), job_id.to_string()),
+    )?;
+
+    Ok(())
+}
+
+// Lease tokens have to keep increasing across restarts so old tokens stay invalid
+pub fn max_lease_token(conn: &Connection) -> Result<u64, Error> {
+    conn.query_row(
+        "SELECT COALESCE(MAX(lease_token), 0) FROM jobs", 
+        (), 
+        |row| row.get(0)
+    )
+}
+
 pub fn update_retry_count(conn: &Connection, job_id: Uuid, count: u32) -> Result<(), Error> {
     conn.execute(
         "UPDATE jobs SET retry_count = ?1 WHERE id = ?2", 
@@ -114,72 +195,128 @@ pub fn update_retry_count(conn: &Connection, job_id: Uuid, count: u32) -> Result
     Ok(())
 }
 
+// Column order `row_to_job` expects
+const JOB_COLUMNS: &str = "id, command, args, status, timestamp, retry_count, max_retries, priority, schedule, is_recurring, next_run, parent_schedule_id, depends_on, timeout_secs, lease_token, lease_expires_at, retry_policy, next_attempt_at, run_at, timezone, misfire_policy, max_catchup, concurrency_policy, not_before, not_after, max_runs, run_count, calendars, blackout_policy, interval_secs, anchor, jitter_secs, env, scheduled_for";
+
+fn row_to_job(row: &Row) -> Result<Job, Error> {
+    let id_str: String = row.get(0)?;
+    let command: String = row.get(1)?;
+    let args_str: String = row.get(2)?;
+    let status_str: String = row.get(3)?;
+    let timestamp_str: String = row.get(4)?;
+
+    let retry_cnt: u32 = row.get(5)?;
+    let max_retry_cnt: u32 = row.get(6)?;
+
+    let priority: String = row.get(7)?;
+
+    let schedule: Option<String> = row.get(8)?;
+    let is_recurring: bool = row.get(9)?;
+    let next_run_str: Option<String> = row.get(10)?;
+    let parent_id: Option<String> = row.get(11)?;
+
+    let depends_on_str: String = row.get(12)?;
+
+    let timeout_secs: Option<u64> = row.get(13)?;
+
+    let lease_token: Option<u64> = row.get(14)?;
+    let lease_expires_str: Option<String> = row.get(15)?;
+
+    let retry_policy_str: Option<String> = row.get(16)?;
+    let next_attempt_str: Option<String> = row.get(17)?;
+
+    let run_at_str: Option<String> = row.get(18)?;
+
+    let timezone: Option<String> = row.get(19)?;
+    let misfire_str: Option<String> = row.get(20)?;
+    let max_catchup: Option<u32> = row.get(21)?;
+    let concurrency_str: Option<String> = row.get(22)?;
+
+    let not_before_str: Option<String> = row.get(23)?;
+    let not_after_str: Option<String> = row.get(24)?;
+    let max_runs: Option<u32> = row.get(25)?;
+    let run_count: Option<u32> = row.get(26)?;
+
+    let calendars_str: Option<String> = row.get(27)?;
+    let blackout_str: Option<String> = row.get(28)?;
+
+    let interval_secs: Option<u64> = row.get(29)?;
+    let anchor_str: Option<String> = row.get(30)?;
+    let jitter_secs: Option<u64> = row.get(31)?;
+
+    let env_str: Option<String> = row.get(32)?;
+    let scheduled_for_str: Option<String> = row.get(33)?;
+
+    // Jobs without a cron store "None" and every job gets a next_run, only a recurring job's one means anything
+    let schedule = schedule.filter(|s| s != "None");
+    let next_run = next_run_str
+        .filter(|_| is_recurring)
+        .and_then(|s| DateTime::parse_from_rfc3339(&s).ok().map(|dt| dt.into()));
+
+    Ok(Job { 
+        id: Uuid::from_str(&id_str).map_err(|_| Error::InvalidColumnType(0, id_str, Type::Text))?, 
+        command, 
+        args: serde_json::from_str::<Vec<String>>(&args_str).map_err(|_| Error::InvalidColumnType(2, args_str, Type::Text))?, 
+        status: JobStatus::from_str(&status_str).map_err(|_| Error::InvalidColumnType(3, status_str, Type::Text))?, 
+        timestamp: DateTime::parse_from_rfc3339(&timestamp_str).map_err(|_| Error::InvalidColumnType(4, timestamp_str, Type::Text))?.into(),
+        
+        retry_count: retry_cnt,
+        max_retries: max_retry_cnt,
+
+        priority: Priority::from_str(&priority).map_err(|_| Error::InvalidColumnType(7, priority, Type::Text))?,
+
+        schedule,
+        interval_secs,
+        anchor: anchor_str.and_then(|s| DateTime::parse_from_rfc3339(&s).ok().map(|dt| dt.into())),
+        jitter_secs,
+        timezone,
+        misfire_policy: misfire_str.and_then(|s| MisfirePolicy::from_str(&s).ok()).unwrap_or_default(),
+        max_catchup: max_catchup.unwrap_or_default(),
+        concurrency_policy: concurrency_str.and_then(|s| ConcurrencyPolicy::from_str(&s).ok()).unwrap_or_default(),
+        not_before: not_before_str.and_then(|s| DateTime::parse_from_rfc3339(&s).ok().map(|dt| dt.into())),
+        not_after: not_after_str.and_then(|s| DateTime::parse_from_rfc3339(&s).ok().map(|dt| dt.into())),
+        max_runs,
+        run_count: run_count.unwrap_or_default(),
+        calendars: calendars_str.and_then(|s| serde_json::from_str::<Option<Vec<String>>>(&s).ok()).flatten(),
+        blackout_policy: blackout_str.and_then(|s| BlackoutPolicy::from_str(&s).ok()).unwrap_or_default(),
+        is_recurring: is_recurring,
+        next_run,
+        parent_schedule_id: parent_id.and_then(|s| Uuid::from_str(&s).ok()),
+        scheduled_for: scheduled_for_str.and_then(|s| DateTime::parse_from_rfc3339(&s).ok().map(|dt| dt.into())),
+
+        depends_on: serde_json::from_str::<Option<Vec<Uuid>>>(&depends_on_str).map_err(|_| Error::InvalidColumnType(12, depends_on_str, Type::Text))?,
+
+        timeout_secs,
+
+        env: env_str.and_then(|s| serde_json::from_str::<Option<HashMap<String, String>>>(&s).ok()).flatten(),
+
+        lease_token,
+        lease_expires_at: lease_expires_str.and_then(|s| DateTime::parse_from_rfc3339(&s).ok().map(|dt| dt.into())),
+
+        retry_policy: retry_policy_str.and_then(|s| serde_json::from_str::<RetryPolicy>(&s).ok()).unwrap_or_default(),
+        next_attempt_at: next_attempt_str.and_then(|s| DateTime::parse_from_rfc3339(&s).ok().map(|dt| dt.into())),
+
+        run_at: run_at_str.and_then(|s| DateTime::parse_from_rfc3339(&s).ok().map(|dt| dt.into()))
+    })
+}
+
 pub fn fetch_from_db(conn: &Connection, status: Option<JobStatus>) -> Result<Vec<Job>, Error> {
     let (mut stmt, param) = if let Some(s) = status {
         (conn.prepare(
-            "SELECT id, command, args, status, timestamp, retry_count, max_retries, priority, schedule, is_recurring, next_run, parent_schedule_id, depends_on
+            &format!("SELECT {}
             FROM jobs 
             WHERE status IN (?1)
-            ORDER BY timestamp ASC"
+            ORDER BY timestamp ASC", JOB_COLUMNS)
         )?, params![s.to_string()])
     } else {
         (conn.prepare(
-            "SELECT id, command, args, status, timestamp, retry_count, max_retries, priority, schedule, is_recurring, next_run, parent_schedule_id, depends_on
+            &format!("SELECT {}
             FROM jobs 
-            ORDER BY timestamp ASC"
+            ORDER BY timestamp ASC", JOB_COLUMNS)
         )?, params![])
     };
 
-    let jobs = stmt.query_map(param, |row| {
-        let id_str: String = row.get(0)?;
-        let command: String = row.get(1)?;
-        let args_str: String = row.get(2)?;
-        let status_str: String = row.get(3)?;
-        let timestamp_str: String = row.get(4)?;
-
-        let retry_cnt: u32 = row.get(5)?;
-        let max_retry_cnt: u32 = row.get(6)?;
-
-        let priority: String = row.get(7)?;
-
-        let schedule: Option<String> = Some(row.get(8)?);
-        let is_recurring: bool = row.get(9)?;
-        let next_run_str: Option<String> = Some(row.get(10)?);
-        let parent_id: Option<String> = row.get(11)?;
-
-        let depends_on_str: String = row.get(12)?;
-
-        let (schedule, is_recurring, next_run, p_id) = if schedule.as_deref() == Some("None") {
-            (None, false, None, None)
-        } else {
-            (
-                schedule,
-                is_recurring,
-                next_run_str.and_then(|s| DateTime::parse_from_rfc3339(&s).ok().map(|dt| dt.into())),
-                parent_id.and_then(|s| Uuid::from_str(&s).ok())
-            )
-        };
-
-        Ok(Job { 
-            id: Uuid::from_str(&id_str).map_err(|_| Error::InvalidColumnType(0, id_str, Type::Text))?, 
-            command, 
-            args: serde_json::from_str::<Vec<String>>(&args_str).map_err(|_| Error::InvalidColumnType(2, args_str, Type::Text))?, 
-            status: JobStatus::from_str(&status_str).map_err(|_| Error::InvalidColumnType(3, status_str, Type::Text))?, 
-            timestamp: DateTime::parse_from_rfc3339(&timestamp_str).map_err(|_| Error::InvalidColumnType(4, timestamp_str, Type::Text))?.into(),
-            
-            retry_count: retry_cnt,
-            max_retries: max_retry_cnt,
-
-            priority: Priority::from_str(&priority).map_err(|_| Error::InvalidColumnType(7, priority, Type::Text))?,
-
-            schedule,
-            is_recurring: is_recurring,
-            next_run,
-            parent_schedule_id: p_id,
-
-            depends_on: serde_json::from_str::<Option<Vec<Uuid>>>(&depends_on_str).map_err(|_| Error::InvalidColumnType(12, depends_on_str, Type::Text))?
-        })
-    })?;
+    let jobs = stmt.query_map(param, row_to_job)?;
     
     let results: Vec<Job> = jobs
         .filter_map(|v| match v {
@@ -194,22 +331,219 @@ pub fn fetch_from_db(conn: &Connection, status: Option<JobStatus>) -> Result<Vec
     Ok(results)
 }
 
-pub fn load_pending_jobs(conn: &Connection) -> Result<Vec<Job>, Error> {
-    let pending = fetch_from_db(conn, Some(JobStatus::PENDING));
-    let running = fetch_from_db(conn, Some(JobStatus::RUNNING));
+// Every job that isn't finished yet, oldest first so the queues keep their order
+pub fn load_unfinished_jobs(conn: &Connection) -> Result<Vec<Job>, Error> {
+    let mut stmt = conn.prepare(
+        &format!("SELECT {}
+        FROM jobs 
+        WHERE status IN ('PENDING', 'RUNNING', 'RETRYING', 'WAITING', 'SCHEDULED', 'PAUSED')
+        ORDER BY timestamp ASC", JOB_COLUMNS)
+    )?;
 
-    if pending.is_ok() && running.is_ok() {
-        Ok([pending.unwrap(), running.unwrap()].concat())
-    } else if running.is_ok() {
-        running
-    } else {
-        pending
-    }
+    let jobs = stmt.query_map([], row_to_job)?;
 
+    Ok(jobs
+        .filter_map(|v| match v {
+            Ok(job) => Some(job),
+            Err(e) => {
+                log::warn!("Skipping malformed job in row: {}", e);
+                None
+            }
+        })
+        .collect())
 }
 
 pub fn get_job_list(conn: &Connection, status: Option<JobStatus>) -> Result<Vec<Job>, Error> {
     let result = fetch_from_db(conn, status);
 
     result
-}
\ No newline at end of file
+}
+
+pub fn get_job(conn: &Connection, job_id: Uuid) -> Result<Option<Job>, Error> {
+    conn.query_row(
+        &format!("SELECT {} FROM jobs WHERE id = ?1", JOB_COLUMNS), 
+        params![job_id.to_string()], 
+        row_to_job
+    ).optional()
+}
+
+// The attempt number is worked out here so it keeps counting up after a requeue resets the retry count
+pub fn insert_attempt(conn: &Connection, job_id: Uuid, worker_id: Uuid, started_at: DateTime<Utc>, finished_at: DateTime<Utc>, result: JobResult) -> Result<(), Error> {
+    conn.execute(
+        "INSERT INTO attempts (job_id, attempt, worker_id, started_at, finished_at, exitcode, stdout, stderr, timed_out) 
+        VALUES (?1, (SELECT COALESCE(MAX(attempt), 0) + 1 FROM attempts WHERE job_id = ?1), ?2, ?3, ?4, ?5, ?6, ?7, ?8)", 
+        params![
+            job_id.to_string(), 
+            worker_id.to_string(), 
+            started_at.to_rfc3339(), 
+            finished_at.to_rfc3339(), 
+            result.exitcode, 
+            result.stdout, 
+            result.stderr, 
+            result.timed_out
+        ],
+    )?;
+
+    Ok(())
+}
+
+pub fn get_attempts(conn: &Connection, job_id: Uuid) -> Result<Vec<JobAttempt>, Error> {
+    let mut stmt = conn.prepare(
+        "SELECT attempt, worker_id, started_at, finished_at, exitcode, stdout, stderr, timed_out 
+        FROM attempts 
+        WHERE job_id = ?1 
+        ORDER BY attempt ASC"
+    )?;
+
+    let attempts = stmt.query_map(params![job_id.to_string()], |row| {
+        let worker_str: String = row.get(1)?;
+        let started_str: String = row.get(2)?;
+        let finished_str: String = row.get(3)?;
+
+        Ok(JobAttempt {
+            attempt: row.get(0)?,
+            worker_id: Uuid::from_str(&worker_str).map_err(|_| Error::InvalidColumnType(1, worker_str, Type::Text))?,
+            started_at: DateTime::parse_from_rfc3339(&started_str).map_err(|_| Error::InvalidColumnType(2, started_str, Type::Text))?.into(),
+            finished_at: DateTime::parse_from_rfc3339(&finished_str).map_err(|_| Error::InvalidColumnType(3, finished_str, Type::Text))?.into(),
+            result: JobResult {
+                exitcode: row.get(4)?,
+                stdout: row.get(5)?,
+                stderr: row.get(6)?,
+                timed_out: row.get(7)?
+            }
+        })
+    })?;
+
+    attempts.collect()
+}
+
+// Newest first
+pub fn get_schedule_runs(conn: &Connection, schedule_id: Uuid, limit: usize) -> Result<Vec<ScheduleRun>, Error> {
+    let mut stmt = conn.prepare(
+        "SELECT j.id, j.status, j.timestamp,
+            (SELECT COUNT(*) FROM attempts a WHERE a.job_id = j.id),
+            (SELECT MIN(a.started_at) FROM attempts a WHERE a.job_id = j.id),
+            (SELECT MAX(a.finished_at) FROM attempts a WHERE a.job_id = j.id),
+            (SELECT r.exitcode FROM results r WHERE r.id = j.id ORDER BY r.rowid DESC LIMIT 1),
+            j.scheduled_for
+        FROM jobs j 
+        WHERE j.parent_schedule_id = ?1 
+        ORDER BY j.timestamp DESC 
+        LIMIT ?2"
+    )?;
+
+    let runs = stmt.query_map(params![schedule_id.to_string(), limit as i64], |row| {
+        let id_str: String = row.get(0)?;
+        let status_str: String = row.get(1)?;
+        let timestamp_str: String = row.get(2)?;
+        let started_str: Option<String> = row.get(4)?;
+        let finished_str: Option<String> = row.get(5)?;
+        let scheduled_for_str: Option<String> = row.get(7)?;
+
+        let started_at: Option<DateTime<Utc>> = started_str.and_then(|s| DateTime::parse_from_rfc3339(&s).ok().map(|dt| dt.into()));
+        let finished_at: Option<DateTime<Utc>> = finished_str.and_then(|s| DateTime::parse_from_rfc3339(&s).ok().map(|dt| dt.into()));
+
+        Ok(ScheduleRun {
+            job_id: Uuid::from_str(&id_str).map_err(|_| Error::InvalidColumnType(0, id_str, Type::Text))?,
+            status: JobStatus::from_str(&status_str).map_err(|_| Error::InvalidColumnType(1, status_str, Type::Text))?,
+            created_at: DateTime::parse_from_rfc3339(&timestamp_str).map_err(|_| Error::InvalidColumnType(2, timestamp_str, Type::Text))?.into(),
+            scheduled_for: scheduled_for_str.and_then(|s| DateTime::parse_from_rfc3339(&s).ok().map(|dt| dt.into())),
+            attempts: row.get(3)?,
+            exitcode: row.get(6)?,
+            started_at,
+            finished_at,
+            duration_ms: started_at.zip(finished_at).map(|(start, finish)| (finish - start).num_milliseconds())
+        })
+    })?;
+
+    runs.collect()
+}
+
+// Calendars
+
+pub fn upsert_calendar(conn: &Connection, calendar: &Calendar) -> Result<(), Error> {
+    conn.execute(
+        "INSERT OR REPLACE INTO calendars (name, windows) VALUES (?1, ?2)", 
+        (
+            &calendar.name, 
+            serde_json::to_string(&calendar.windows).map_err(|err| Error::ToSqlConversionFailure(Box::new(err)))?
+        )
+    )?;
+
+    Ok(())
+}
+
+pub fn get_calendars(conn: &Connection) -> Result<Vec<Calendar>, Error> {
+    let mut stmt = conn.prepare("SELECT name, windows FROM calendars ORDER BY name ASC")?;
+
+    let calendars = stmt.query_map([], |row| {
+        let name: String = row.get(0)?;
+        let windows_str: String = row.get(1)?;
+
+        Ok(Calendar {
+            name,
+            windows: serde_json::from_str::<Vec<BlackoutWindow>>(&windows_str).map_err(|_| Error::InvalidColumnType(1, windows_str, Type::Text))?
+        })
+    })?;
+
+    calendars.collect()
+}
+
+pub fn delete_calendar(conn: &Connection, name: &str) -> Result<bool, Error> {
+    let deleted = conn.execute("DELETE FROM calendars WHERE name = ?1", params![name])?;
+
+    Ok(deleted > 0)
+}
+
+// Dead letters
+
+pub fn insert_dead_letter(conn: &Connection, job_id: Uuid, reason: String, failed_at: DateTime<Utc>) -> Result<(), Error> {
+    conn.execute(
+        "INSERT OR REPLACE INTO dead_letters (id, reason, failed_at) VALUES (?1, ?2, ?3)", 
+        (job_id.to_string(), reason, failed_at.to_rfc3339()),
+    )?;
+
+    Ok(())
+}
+
+pub fn get_dead_letters(conn: &Connection) -> Result<Vec<DeadLetter>, Error> {
+    let mut stmt = conn.prepare(
+        "SELECT id, reason, failed_at FROM dead_letters ORDER BY failed_at ASC"
+    )?;
+
+    let rows = stmt.query_map((), |row| {
+        let id_str: String = row.get(0)?;
+        let reason: String = row.get(1)?;
+        let failed_at_str: String = row.get(2)?;
+
+        Ok((
+            Uuid::from_str(&id_str).map_err(|_| Error::InvalidColumnType(0, id_str, Type::Text))?,
+            reason,
+            DateTime::parse_from_rfc3339(&failed_at_str).map_err(|_| Error::InvalidColumnType(2, failed_at_str, Type::Text))?.into()
+        ))
+    })?.collect::<Result<Vec<(Uuid, String, DateTime<Utc>)>, Error>>()?;
+
+    let mut letters = vec![];
+    for (job_id, reason, failed_at) in rows {
+        match get_job(conn, job_id)? {
+            Some(job) => letters.push(DeadLetter {
+                job,
+                reason,
+                failed_at,
+                attempts: get_attempts(conn, job_id)?
+            }),
+            None => log::warn!("Skipping dead letter for missing job id: {}", job_id)
+        }
+    }
+
+    Ok(letters)
+}
+
+pub fn delete_dead_letter(conn: &Connection, job_id: Uuid) -> Result<bool, Error> {
+    let deleted = conn.execute(
+        "DELETE FROM dead_letters WHERE id = ?1", 
+        params![job_id.to_string()],
+    )?;
+
+    Ok(deleted > 0)
+}
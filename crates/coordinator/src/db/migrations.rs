@@ -0,0 +1,296 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, Error, OptionalExtension};
+
+// Databases made before versioning were created straight from the latest CREATE TABLE, so every
+// step has to cope with the change already being there: tables use IF NOT EXISTS and columns go
+// through `add_column_if_missing`
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    apply: fn(&Connection) -> Result<(), Error>
+}
+
+// Append only, a released migration must never change
+pub const MIGRATIONS: &[Migration] = &[
+    Migration { version: 1, name: "create jobs and results", apply: create_jobs_and_results },
+    Migration { version: 2, name: "job timeouts", apply: job_timeouts },
+    Migration { version: 3, name: "job leases", apply: job_leases },
+    Migration { version: 4, name: "retry policies", apply: retry_policies },
+    Migration { version: 5, name: "dead letter queue", apply: dead_letter_queue },
+    Migration { version: 6, name: "job attempts", apply: job_attempts },
+    Migration { version: 7, name: "delayed jobs", apply: delayed_jobs },
+    Migration { version: 8, name: "schedule policies and bounds", apply: schedule_policies },
+    Migration { version: 9, name: "blackout calendars", apply: blackout_calendars },
+    Migration { version: 10, name: "interval schedules", apply: interval_schedules },
+    Migration { version: 11, name: "job env and logical run time", apply: job_env },
+    Migration { version: 12, name: "status and schedule indexes", apply: job_indexes },
+    Migration { version: 13, name: "backfill result timeouts", apply: backfill_timed_out },
+];
+
+pub struct AppliedMigration {
+    pub version: u32,
+    pub name: String,
+    pub applied_at: DateTime<Utc>
+}
+
+// Runs every migration the database hasn't seen yet, each in its own transaction. Returns the ones applied
+pub fn migrate(conn: &mut Connection) -> Result<Vec<&'static Migration>, Error> {
+    create_version_table(conn)?;
+
+    let pending = pending(conn)?;
+
+    for migration in &pending {
+        let tx = conn.transaction()?;
+
+        (migration.apply)(&tx)?;
+        record(&tx, migration)?;
+
+        tx.commit()?;
+
+        log::info!("Applied migration {}: {}", migration.version, migration.name);
+    }
+
+    Ok(pending)
+}
+
+// Applies the pending migrations in one transaction and rolls it back, so a broken one shows up without touching the database
+pub fn dry_run(conn: &mut Connection) -> Result<Vec<&'static Migration>, (&'static Migration, Error)> {
+    let tx = conn.transaction().map_err(|err| (&MIGRATIONS[0], err))?;
+
+    create_version_table(&tx).map_err(|err| (&MIGRATIONS[0], err))?;
+
+    let pending = pending(&tx).map_err(|err| (&MIGRATIONS[0], err))?;
+
+    for migration in &pending {
+        (migration.apply)(&tx).map_err(|err| (*migration, err))?;
+        record(&tx, migration).map_err(|err| (*migration, err))?;
+    }
+
+    tx.rollback().map_err(|err| (&MIGRATIONS[0], err))?;
+
+    Ok(pending)
+}
+
+pub fn current_version(conn: &Connection) -> Result<u32, Error> {
+    if !table_exists(conn, "schema_version")? {
+        return Ok(0);
+    }
+
+    conn.query_row("SELECT COALESCE(MAX(version), 0) FROM schema_version", (), |row| row.get(0))
+}
+
+pub fn applied(conn: &Connection) -> Result<Vec<AppliedMigration>, Error> {
+    if !table_exists(conn, "schema_version")? {
+        return Ok(vec![]);
+    }
+
+    let mut stmt = conn.prepare("SELECT version, name, applied_at FROM schema_version ORDER BY version ASC")?;
+
+    let rows = stmt.query_map((), |row| {
+        let applied_str: String = row.get(2)?;
+
+        Ok(AppliedMigration {
+            version: row.get(0)?,
+            name: row.get(1)?,
+            applied_at: DateTime::parse_from_rfc3339(&applied_str).map(|dt| dt.into()).unwrap_or_default()
+        })
+    })?;
+
+    rows.collect()
+}
+
+pub fn pending(conn: &Connection) -> Result<Vec<&'static Migration>, Error> {
+    let version = current_version(conn)?;
+
+    Ok(MIGRATIONS.iter().filter(|m| m.version > version).collect())
+}
+
+fn create_version_table(conn: &Connection) -> Result<(), Error> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            name TEXT,
+            applied_at TIMESTAMP
+        );",
+        ()
+    )?;
+
+    Ok(())
+}
+
+fn record(conn: &Connection, migration: &Migration) -> Result<(), Error> {
+    conn.execute(
+        "INSERT INTO schema_version (version, name, applied_at) VALUES (?1, ?2, ?3)",
+        (migration.version, migration.name, Utc::now().to_rfc3339())
+    )?;
+
+    Ok(())
+}
+
+fn table_exists(conn: &Connection, table: &str) -> Result<bool, Error> {
+    conn.query_row(
+        "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        [table],
+        |_| Ok(())
+    ).optional().map(|found| found.is_some())
+}
+
+// SQLite has no ADD COLUMN IF NOT EXISTS
+pub fn add_column_if_missing(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<(), Error> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt.query_map((), |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<String>, Error>>()?
+        .iter()
+        .any(|name| name == column);
+
+    if !exists {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), ())?;
+    }
+
+    Ok(())
+}
+
+// Migrations
+
+fn create_jobs_and_results(conn: &Connection) -> Result<(), Error> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS jobs (
+            id UUID PRIMARY Key,
+            command TEXT,
+            args TEXT,
+            status TEXT,
+            timestamp TIMESTAMP,
+
+            retry_count INTEGER,
+            max_retries INTEGER,
+
+            priority TEXT,
+
+            schedule TEXT,
+            next_run TIMESTAMP,
+            is_recurring BOOL,
+            parent_schedule_id UUID,
+
+            depends_on TEXT
+        );",
+        ()
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS results (
+            id UUID,
+            exitcode INTEGER,
+            stdout TEXT,
+            stderr TEXT,
+            FOREIGN KEY(id) REFERENCES jobs(id)
+        );",
+        ()
+    )?;
+
+    Ok(())
+}
+
+fn job_timeouts(conn: &Connection) -> Result<(), Error> {
+    add_column_if_missing(conn, "jobs", "timeout_secs", "INTEGER")?;
+    add_column_if_missing(conn, "results", "timed_out", "BOOL")
+}
+
+fn job_leases(conn: &Connection) -> Result<(), Error> {
+    add_column_if_missing(conn, "jobs", "lease_token", "INTEGER")?;
+    add_column_if_missing(conn, "jobs", "lease_expires_at", "TIMESTAMP")
+}
+
+fn retry_policies(conn: &Connection) -> Result<(), Error> {
+    add_column_if_missing(conn, "jobs", "retry_policy", "TEXT")?;
+    add_column_if_missing(conn, "jobs", "next_attempt_at", "TIMESTAMP")
+}
+
+fn dead_letter_queue(conn: &Connection) -> Result<(), Error> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS dead_letters (
+            id UUID PRIMARY KEY,
+            reason TEXT,
+            failed_at TIMESTAMP,
+            FOREIGN KEY(id) REFERENCES jobs(id)
+        );",
+        ()
+    )?;
+
+    Ok(())
+}
+
+fn job_attempts(conn: &Connection) -> Result<(), Error> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS attempts (
+            job_id UUID,
+            attempt INTEGER,
+            worker_id UUID,
+            started_at TIMESTAMP,
+            finished_at TIMESTAMP,
+            exitcode INTEGER,
+            stdout TEXT,
+            stderr TEXT,
+            timed_out BOOL,
+            FOREIGN KEY(job_id) REFERENCES jobs(id)
+        );",
+        ()
+    )?;
+
+    Ok(())
+}
+
+fn delayed_jobs(conn: &Connection) -> Result<(), Error> {
+    add_column_if_missing(conn, "jobs", "run_at", "TIMESTAMP")
+}
+
+fn schedule_policies(conn: &Connection) -> Result<(), Error> {
+    add_column_if_missing(conn, "jobs", "timezone", "TEXT")?;
+    add_column_if_missing(conn, "jobs", "misfire_policy", "TEXT")?;
+    add_column_if_missing(conn, "jobs", "max_catchup", "INTEGER")?;
+    add_column_if_missing(conn, "jobs", "concurrency_policy", "TEXT")?;
+    add_column_if_missing(conn, "jobs", "not_before", "TIMESTAMP")?;
+    add_column_if_missing(conn, "jobs", "not_after", "TIMESTAMP")?;
+    add_column_if_missing(conn, "jobs", "max_runs", "INTEGER")?;
+    add_column_if_missing(conn, "jobs", "run_count", "INTEGER")
+}
+
+fn blackout_calendars(conn: &Connection) -> Result<(), Error> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS calendars (
+            name TEXT PRIMARY KEY,
+            windows TEXT
+        );",
+        ()
+    )?;
+
+    add_column_if_missing(conn, "jobs", "calendars", "TEXT")?;
+    add_column_if_missing(conn, "jobs", "blackout_policy", "TEXT")
+}
+
+fn interval_schedules(conn: &Connection) -> Result<(), Error> {
+    add_column_if_missing(conn, "jobs", "interval_secs", "INTEGER")?;
+    add_column_if_missing(conn, "jobs", "anchor", "TIMESTAMP")?;
+    add_column_if_missing(conn, "jobs", "jitter_secs", "INTEGER")
+}
+
+fn job_env(conn: &Connection) -> Result<(), Error> {
+    add_column_if_missing(conn, "jobs", "env", "TEXT")?;
+    add_column_if_missing(conn, "jobs", "scheduled_for", "TIMESTAMP")
+}
+
+fn job_indexes(conn: &Connection) -> Result<(), Error> {
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_jobs_status ON jobs(status)", ())?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_jobs_parent_schedule ON jobs(parent_schedule_id)", ())?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_attempts_job ON attempts(job_id)", ())?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_results_id ON results(id)", ())?;
+
+    Ok(())
+}
+
+// Results written before job timeouts were added have no timed_out, none of those runs could have timed out
+fn backfill_timed_out(conn: &Connection) -> Result<(), Error> {
+    conn.execute("UPDATE results SET timed_out = 0 WHERE timed_out IS NULL", ())?;
+    conn.execute("UPDATE attempts SET timed_out = 0 WHERE timed_out IS NULL", ())?;
+
+    Ok(())
+}
@@ -8,15 +8,51 @@ use actix_web::{
 use std::{
     io::Result, sync::{Arc, LazyLock}, time::Duration
 };
-use tokio::{
-    sync::Mutex, 
-    time::sleep
-};
-use rusqlite::Connection;
+use tokio::time::sleep;
+use chrono::Utc;
+use clap::{Parser, Subcommand};
 
 use crate::queue::JobQueue;
 
-mod api; mod queue; mod db; mod metrics;
+mod api; mod commands; mod queue; mod db; mod metrics; mod schedule; mod store;
+
+#[derive(Parser)]
+#[command(about = "Coordinator of the job scheduler, runs the API server when no command is given")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Apply pending database migrations and exit
+    Migrate {
+        #[arg(long, conflicts_with = "dry_run", help = "Show the schema version and which migrations are applied or pending")]
+        status: bool,
+
+        #[arg(long, help = "Check that pending migrations apply cleanly, then roll them back")]
+        dry_run: bool,
+    },
+    /// Compare SQLite write throughput with and without the writer thread, using temporary databases
+    Bench {
+        #[arg(long, default_value_t = 2000, help = "Jobs to write in each run")]
+        jobs: usize,
+    },
+    /// Simulate hundreds of workers against an in-memory queue, with one lock and with the queue's own locks
+    Loadtest {
+        #[arg(long, default_value_t = 300, help = "Simulated workers, one thread each")]
+        workers: usize,
+
+        #[arg(long, default_value_t = 10000, help = "Jobs to run through the queue")]
+        jobs: usize,
+
+        #[arg(long, default_value_t = 8, help = "Threads looking up job details while the workers run")]
+        readers: usize,
+
+        #[arg(long, help = "Use a temporary SQLite database instead of the in-memory store")]
+        sqlite: bool,
+    },
+}
 
 static COORDINATOR_ADDR: LazyLock<String> = LazyLock::new(|| {
     dotenvy::dotenv().ok();
@@ -36,17 +72,36 @@ static COORDINATOR_ADDR: LazyLock<String> = LazyLock::new(|| {
 #[actix_web::main]
 async fn main() -> Result<()> {
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
+    dotenvy::dotenv().ok();
 
-    log::info!("Initalizing database..");
-    let db_connection = Connection::open("scheduler.db").unwrap();
-    let _ = db::init(&db_connection);
-
-    let db_close = db_connection.close();
-    if db_close.is_err() {
-        log::error!("The DB connection failed to close after initialization");
+    match Cli::parse().command {
+        Some(Commands::Migrate { status, dry_run }) => {
+            commands::migrate::run(status, dry_run);
+            return Ok(());
+        },
+        Some(Commands::Bench { jobs }) => {
+            commands::bench::run(jobs.max(1));
+            return Ok(());
+        },
+        Some(Commands::Loadtest { workers, jobs, readers, sqlite }) => {
+            commands::loadtest::run(workers.max(1), jobs.max(1), readers, sqlite);
+            return Ok(());
+        },
+        None => {}
     }
 
-    let queue = Arc::new(Mutex::new(JobQueue::new()));
+    let backend = store::backend().unwrap_or_else(|err| {
+        log::error!("{}, exiting program.", err);
+        std::process::exit(1);
+    });
+
+    log::info!("Initalizing {} store..", backend);
+    let job_store = store::open(backend).unwrap_or_else(|err| {
+        log::error!("DB Error: {}, exiting program.", err);
+        std::process::exit(1);
+    });
+
+    let queue = Arc::new(JobQueue::new(job_store));
     
     let checker_queue = queue.clone();
     tokio::spawn(async move {
@@ -55,20 +110,40 @@ async fn main() -> Result<()> {
 
             log::info!("Checking workers...");
 
-            api::check_workers(q).await;
+            api::check_workers(q.clone()).await;
+            api::evict_finished_jobs(q).await;
             sleep(Duration::from_secs(30)).await;    
         }
     });
 
     let schedule_queue = queue.clone();
+    let schedule_wakeup = queue.schedule_notifier();
     tokio::spawn(async move {
         loop {
             let q = schedule_queue.clone();
-            
-            log::info!("Check scheduled jobs");
 
-            api::check_schedules(q).await;
-            sleep(Duration::from_secs(60)).await;
+            // Sleeps until the earliest schedule is due, or a new schedule is added
+            match api::check_schedules(q).await {
+                Some(next_run) => {
+                    let wait = (next_run - Utc::now()).to_std().unwrap_or_default();
+
+                    tokio::select! {
+                        _ = sleep(wait) => {},
+                        _ = schedule_wakeup.notified() => {}
+                    }
+                },
+                None => schedule_wakeup.notified().await
+            }
+        }
+    });
+
+    let delayed_queue = queue.clone();
+    tokio::spawn(async move {
+        loop {
+            let q = delayed_queue.clone();
+
+            api::check_delayed_jobs(q).await;
+            sleep(Duration::from_secs(1)).await;
         }
     });
 
@@ -104,6 +179,26 @@ async fn main() -> Result<()> {
                             .route("/job", web::post().to(api::submit_job))
                             .route("/job/list", web::post().to(api::list_jobs))
                             .route("/job/{job_id}", web::get().to(api::job_details))
+                            .route("/job/{job_id}/cancel", web::post().to(api::cancel_job))
+
+                            .route("/dlq", web::get().to(api::list_dead_letters))
+                            .route("/dlq", web::delete().to(api::purge_all_dead_letters))
+                            .route("/dlq/requeue", web::post().to(api::requeue_all_dead_letters))
+                            .route("/dlq/{job_id}/requeue", web::post().to(api::requeue_dead_letter))
+                            .route("/dlq/{job_id}", web::delete().to(api::purge_dead_letter))
+
+                            .route("/schedule/{job_id}", web::patch().to(api::update_schedule))
+                            .route("/schedule/{job_id}", web::delete().to(api::delete_schedule))
+                            .route("/schedule/{job_id}/pause", web::post().to(api::pause_schedule))
+                            .route("/schedule/{job_id}/resume", web::post().to(api::resume_schedule))
+                            .route("/schedule/{job_id}/runs", web::get().to(api::schedule_runs))
+                            .route("/schedule/{job_id}/preview", web::get().to(api::schedule_preview))
+                            .route("/schedule/{job_id}/backfill", web::post().to(api::backfill_schedule))
+
+                            .route("/calendar", web::get().to(api::list_calendars))
+                            .route("/calendar/{name}", web::put().to(api::put_calendar))
+                            .route("/calendar/{name}", web::get().to(api::get_calendar))
+                            .route("/calendar/{name}", web::delete().to(api::delete_calendar))
                     )
             )
     })
@@ -8,6 +8,13 @@ pub static JOBS_COMPLETED_TOTAL: LazyLock<Counter> = LazyLock::new(|| {
     ).unwrap()
 });
 
+pub static JOBS_CANCELED_TOTAL: LazyLock<Counter> = LazyLock::new(|| {
+    register_counter!(
+        "jobs_canceled_total",
+        "Total number of canceled jobs"
+    ).unwrap()
+});
+
 pub static JOBS_SUBMITTED_TOTAL: LazyLock<CounterVec> = LazyLock::new(|| {
     register_counter_vec!(
         "jobs_submitted_total",
@@ -23,6 +30,20 @@ pub static JOBS_FAILED_TOTAL: LazyLock<Counter> = LazyLock::new(||{
     ).unwrap()
 });
 
+pub static JOBS_DEAD_LETTERED_TOTAL: LazyLock<Counter> = LazyLock::new(|| {
+    register_counter!(
+        "jobs_dead_lettered_total",
+        "Total number of jobs moved to the dead letter queue"
+    ).unwrap()
+});
+
+pub static JOBS_TIMED_OUT_TOTAL: LazyLock<Counter> = LazyLock::new(|| {
+    register_counter!(
+        "jobs_timed_out_total",
+        "Total number of job attempts killed for exceeding their timeout"
+    ).unwrap()
+});
+
 pub static QUEUE_DEPTH: LazyLock<GaugeVec> = LazyLock::new(|| {
     register_gauge_vec!(
         "queue_depth",
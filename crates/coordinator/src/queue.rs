@@ -1,368 +1,1438 @@
 use common::{
+    calendar::{BlackoutPolicy, Calendar},
     job::{
+        ConcurrencyPolicy,
         JobResult, 
         JobStatus, 
+        MisfirePolicy,
         Priority,
+        RetryPolicy,
         Job, 
     }, 
     message::{
+        DeadLetter,
         GetJobStatusResponse, 
+        ScheduleRun,
+        UpdateScheduleRequest,
         WorkerHeartbeat, 
         WorkerStatus,
         WorkerInfo, 
     }
 };
-use cron::Schedule;
-use std::{collections::{
-    HashMap, VecDeque 
-}, process::exit, str::FromStr};
+use std::{cmp::Reverse, collections::{
+    BTreeSet, BinaryHeap, HashMap, HashSet, VecDeque 
+}, process::exit, str::FromStr, sync::{Arc, LazyLock, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard}};
 use chrono::{
+    DateTime,
     Duration, 
     Utc
 };
-use rusqlite::Connection;
+use tokio::sync::Notify;
 use uuid::Uuid;
 
-use crate::{db, metrics};
+use crate::{metrics, schedule, store::{JobStore, StoreError}};
+
+// Most runs one backfill request can queue
+pub const MAX_BACKFILL_RUNS: usize = 1000;
+
+// Longest a retry can be backed off for, a week
+pub const MAX_RETRY_DELAY_SECS: u64 = 7 * 24 * 60 * 60;
+
+// Seconds a worker holds a job before it has to renew the lease with a heartbeat
+const LEASE_DURATION: i64 = 30;
+
+// Longest JOB_RETENTION_SECS can be, a week
+const MAX_JOB_RETENTION_SECS: i64 = 7 * 24 * 60 * 60;
+
+// How long a finished job stays in memory, lookups go to the store after that
+static JOB_RETENTION: LazyLock<Duration> = LazyLock::new(|| {
+    match std::env::var("JOB_RETENTION_SECS") {
+        Ok(secs) => {
+            i64::from_str(&secs).ok()
+                .filter(|secs| (0..=MAX_JOB_RETENTION_SECS).contains(secs))
+                .and_then(Duration::try_seconds)
+                .unwrap_or_else(|| {
+                    log::info!("JOB_RETENTION_SECS must be a number of seconds between 0 and {}. Defaulting to 300 seconds.", MAX_JOB_RETENTION_SECS);
+                    Duration::seconds(300)
+                })
+        },
+        Err(_) => {
+            log::info!("JOB_RETENTION_SECS is not present. Defaulting to 300 seconds.");
+            Duration::seconds(300)
+        }
+    }
+});
 
+// Each part has its own lock so heartbeats, lookups and dispatch don't queue up behind each other.
+// Locks are only held for one step and never across an await. Anything that needs more than one
+// takes them in this order: schedules, calendars, jobs, ready, workers. The store is called with
+// locks held but never calls back into the queue
 pub struct JobQueue {
+    schedules: RwLock<Schedules>,
+    calendars: RwLock<HashMap<String, Calendar>>,
+    jobs: RwLock<JobIndex>,
+    ready: Mutex<ReadyQueues>,
+    workers: RwLock<WorkerRegistry>,
+
+    schedule_wakeup: Arc<Notify>,
+    store: Box<dyn JobStore>
+}
+
+// Every active job that isn't a schedule plus the recently finished ones, this is what decides a job's status
+#[derive(Default)]
+struct JobIndex {
     jobs: HashMap<Uuid, Job>,
-    schedules: HashMap<Uuid, Job>,
     results: HashMap<Uuid, JobResult>,
+    next_lease_token: u64,
+
+    // COMPLETED, FAILED and CANCELED jobs ordered by when they finished, for `evict_finished_jobs`
+    finished: BTreeSet<(DateTime<Utc>, Uuid)>,
+    // Final status of recently evicted jobs and when they were evicted, so dependency checks and cancels
+    // don't go to the store. Kept for another JOB_RETENTION_SECS, `evicted_order` has them oldest first
+    evicted: HashMap<Uuid, (JobStatus, DateTime<Utc>)>,
+    evicted_order: VecDeque<(DateTime<Utc>, Uuid)>
+}
+
+impl JobIndex {
+    fn evicted_status(&self, job_id: Uuid) -> Option<&JobStatus> {
+        self.evicted.get(&job_id).map(|(status, _)| status)
+    }
+
+    fn remember_evicted(&mut self, job_id: Uuid, status: JobStatus, now: DateTime<Utc>) {
+        self.evicted.insert(job_id, (status, now));
+        self.evicted_order.push_back((now, job_id));
+    }
+
+    // An entry that was replaced since is left to its newer one
+    fn forget_evicted_before(&mut self, cutoff: DateTime<Utc>) {
+        while let Some((evicted_at, job_id)) = self.evicted_order.front().copied() && evicted_at <= cutoff {
+            self.evicted_order.pop_front();
+
+            if self.evicted.get(&job_id).is_some_and(|(_, at)| *at == evicted_at) {
+                self.evicted.remove(&job_id);
+            }
+        }
+    }
+}
 
+// Copies of the jobs waiting for a worker. A copy whose job was canceled or finished while it
+// sat here is dropped when it comes up, the index has the final say
+#[derive(Default)]
+struct ReadyQueues {
     pending_high: VecDeque<Job>,
     pending_medium: VecDeque<Job>,
     pending_low: VecDeque<Job>,
 
+    // SCHEDULED one-off jobs ordered by when they're due
+    delayed: BTreeSet<(DateTime<Utc>, Uuid)>
+}
+
+#[derive(Default)]
+struct Schedules {
+    schedules: HashMap<Uuid, Job>,
+
+    // Min-heap of (next_run, schedule id), entries that no longer match the schedule's next_run are skipped
+    timers: BinaryHeap<Reverse<(DateTime<Utc>, Uuid)>>,
+    backfills: HashMap<Uuid, Backfill>
+}
+
+#[derive(Default)]
+struct WorkerRegistry {
     workers: HashMap<Uuid, WorkerInfo>,
-    connection: Connection
+    cancel_requests: HashMap<Uuid, Vec<Uuid>>
+}
+
+pub enum CancelError {
+    NotFound,
+    AlreadyFinished(JobStatus)
+}
+
+pub enum CalendarError {
+    NotFound,
+    InUse(Vec<Uuid>)
+}
+
+pub enum ScheduleError {
+    NotFound,
+    InvalidSchedule,
+    Finished,
+    TooManyRuns
+}
+
+// What a worker's result did to its job
+#[derive(PartialEq)]
+pub enum ResultOutcome {
+    // Canceled while it ran, the output is kept but the status stays CANCELED
+    Canceled,
+    Retrying,
+    NotRetryable,
+    OutOfRetries,
+    Completed
+}
+
+// Occurrences a backfill hasn't started yet, let out a few at a time so they don't flood the pending queues
+struct Backfill {
+    pending: VecDeque<DateTime<Utc>>,
+    in_flight: HashSet<Uuid>,
+    max_in_flight: usize
+}
+
+impl ReadyQueues {
+    fn push(&mut self, job: Job) {
+        match job.priority {
+            Priority::HIGH => self.pending_high.push_back(job),
+            Priority::MEDIUM => self.pending_medium.push_back(job),
+            Priority::LOW => self.pending_low.push_back(job),
+        }
+    }
+
+    // Takes the first job in priority order that isn't still backing off from a failed attempt
+    fn pop_ready(&mut self) -> Option<Job> {
+        let now = Utc::now();
+
+        for pending in [&mut self.pending_high, &mut self.pending_medium, &mut self.pending_low] {
+            if let Some(pos) = pending.iter().position(|j| j.next_attempt_at.is_none_or(|t| t <= now)) {
+                return pending.remove(pos);
+            }
+        }
+
+        None
+    }
+
+    fn remove(&mut self, job_id: Uuid) -> Option<Job> {
+        for pending in [&mut self.pending_high, &mut self.pending_medium, &mut self.pending_low] {
+            if let Some(pos) = pending.iter().position(|j| j.id == job_id) {
+                return pending.remove(pos);
+            }
+        }
+
+        None
+    }
 }
 
 impl JobQueue {
-    pub fn new() -> Self {
-        let mut queue = JobQueue {
-            jobs: HashMap::new(), 
-            schedules: HashMap::new(),
-            results: HashMap::new(),
-
-            pending_high: VecDeque::new(), 
-            pending_medium: VecDeque::new(), 
-            pending_low: VecDeque::new(),
-
-            workers: HashMap::new(),
-            connection: Connection::open("scheduler.db").unwrap_or_else(|e| {
-                log::error!("DB Error: Failed to open database, exiting program.\n Error: {}", e); 
+    pub fn new(store: Box<dyn JobStore>) -> Self {
+        let next_lease_token = store.max_lease_token().unwrap_or_else(|e| {
+                log::error!("DB Error: Failed to load the last lease token, exiting program.\n Error: {}", e); 
                 exit(1); 
-            })
+            });
+
+        let calendars = store.get_calendars().unwrap_or_else(|e| {
+                log::error!("DB Error: Failed to load calendars, exiting program.\n Error: {}", e); 
+                exit(1); 
+            });
+
+        let queue = JobQueue {
+            schedules: RwLock::default(),
+            calendars: RwLock::new(calendars.into_iter().map(|calendar| (calendar.name.clone(), calendar)).collect()),
+            jobs: RwLock::new(JobIndex { next_lease_token, ..Default::default() }),
+            ready: Mutex::default(),
+            workers: RwLock::default(),
+
+            schedule_wakeup: Arc::new(Notify::new()),
+            store
         };
 
-        let jobs = db::load_pending_jobs(&queue.connection).unwrap_or_else(|e| {
-                log::error!("DB Error: Failed to load pending jobs, exiting program.\n Error: {}", e); 
+        let jobs = queue.store.load_unfinished_jobs().unwrap_or_else(|e| {
+                log::error!("DB Error: Failed to load unfinished jobs, exiting program.\n Error: {}", e); 
                 exit(1); 
             });
-        
+
+        let mut schedules = vec![];
+
         for job in jobs {
             if job.is_recurring {
-                queue.schedules.insert(job.id, job.clone());
-                log::info!("Loading schedule into HashMap: id={}, schedule={:?}", job.id, job.schedule);
+                schedules.push(job);
             } else {
-                queue.jobs.insert(job.id, job.clone());
+                queue.load_job(job);
+            }
+        }
 
+        // After the other jobs so catch-up runs can see which earlier runs are still going
+        for job in schedules {
+            log::info!("Loading schedule into HashMap: id={}, schedule={:?}", job.id, job.schedule);
+            queue.load_schedule(job);
+        }
+
+        queue
+    }
+
+    // A panic while holding one of these can't leave its maps half written, so a poisoned lock is still usable
+
+    fn schedules(&self) -> RwLockReadGuard<'_, Schedules> {
+        self.schedules.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn schedules_mut(&self) -> RwLockWriteGuard<'_, Schedules> {
+        self.schedules.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn calendars(&self) -> RwLockReadGuard<'_, HashMap<String, Calendar>> {
+        self.calendars.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn calendars_mut(&self) -> RwLockWriteGuard<'_, HashMap<String, Calendar>> {
+        self.calendars.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn jobs(&self) -> RwLockReadGuard<'_, JobIndex> {
+        self.jobs.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn jobs_mut(&self) -> RwLockWriteGuard<'_, JobIndex> {
+        self.jobs.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn ready(&self) -> MutexGuard<'_, ReadyQueues> {
+        self.ready.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn workers(&self) -> RwLockReadGuard<'_, WorkerRegistry> {
+        self.workers.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn workers_mut(&self) -> RwLockWriteGuard<'_, WorkerRegistry> {
+        self.workers.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Puts an unfinished job back where it was before the restart
+    fn load_job(&self, mut job: Job) {
+        match job.status {
+            JobStatus::SCHEDULED => {
+                self.ready().delayed.insert((job.run_at.unwrap_or_else(Utc::now), job.id));
+            },
+            // Its worker is likely still running it, the lease gets a fresh window for the worker to
+            // re-register and renew it before the job is re-queued. A job without a token can't be
+            // renewed, it only gets the window in memory and is re-queued once that runs out
+            JobStatus::RUNNING => {
+                let grace = Utc::now() + Duration::seconds(LEASE_DURATION);
+
+                if job.lease_expires_at.is_none_or(|t| t < grace) {
+                    job.lease_expires_at = Some(grace);
+
+                    if let Some(token) = job.lease_token {
+                        match self.store.update_job_lease(job.id, token, grace) {
+                            Ok(_) => {},
+                            Err(err) => {log::error!("DB Error: Failed to extend lease for job id: {}\n Error output: {:?}", job.id, err)}   
+                        } 
+                    }
+                }
+            },
+            // PENDING, RETRYING (still backing off until next_attempt_at) and WAITING on dependencies
+            _ => {
                 metrics::QUEUE_DEPTH.with_label_values(&[&job.priority.to_string()]).inc();
 
-                match job.priority {
-                    Priority::HIGH => queue.pending_high.push_back(job),
-                    Priority::MEDIUM => queue.pending_medium.push_back(job),
-                    Priority::LOW => queue.pending_low.push_back(job),
+                if job.status == JobStatus::WAITING {
+                    metrics::JOBS_WAITING_TOTAL.inc();
                 }
+
+                self.ready().push(job.clone());
+            }
+        }
+
+        log::info!("Reloaded {:?} job id: {}", job.status, job.id);
+
+        self.jobs_mut().jobs.insert(job.id, job);
+    }
+
+    // Runs missed while the coordinator was down are handled by the schedule's misfire policy,
+    // after that the schedule carries on from now
+    fn load_schedule(&self, mut job: Job) {
+        if job.status != JobStatus::PENDING {
+            self.schedules_mut().schedules.insert(job.id, job);
+            return;
+        }
+
+        let now = Utc::now();
+
+        if job.next_run.is_none_or(|next| next < now) {
+            let missed_times = self.missed_runs(&job, now);
+            let missed = missed_times.len();
+
+            let catch_up = match job.misfire_policy {
+                MisfirePolicy::SKIP => 0,
+                MisfirePolicy::RUN_ONCE => missed.min(1),
+                MisfirePolicy::RUN_ALL => missed.min(job.max_catchup as usize)
+            }.min(schedule::runs_left(&job).map_or(usize::MAX, |left| left as usize));
+
+            if missed > 0 {
+                let missed_str = if missed > job.max_catchup as usize { format!("over {}", job.max_catchup) } else { missed.to_string() };
+                log::info!("Schedule {} missed {} run(s) while down, misfire policy {} starts {}", job.id, missed_str, job.misfire_policy, catch_up);
+            }
+
+            // Catch-up runs stand for the earliest missed occurrences, backfills don't survive a restart
+            for run_time in missed_times.into_iter().take(catch_up) {
+                self.run_schedule(&mut job, run_time, None);
+            }
+
+            job.next_run = schedule::next_occurrence(&job, now);
+
+            match job.next_run {
+                Some(next_time) => {
+                    match self.store.update_schedule_run(job.id, next_time) {
+                        Ok(_) => {},
+                        Err(err) => {log::error!("DB Error: Failed update schedule time for job id: {}\n Error output: {:?}", job.id, err)}   
+                    } 
+                },
+                None => {
+                    self.finish_schedule(&mut self.schedules_mut(), job);
+                    return;
                 }
+            }
         }
 
-        queue
+        let mut schedules = self.schedules_mut();
+
+        if let Some(next_time) = job.next_run {
+            schedules.timers.push(Reverse((next_time, job.id)));
+        }
+
+        schedules.schedules.insert(job.id, job);
+    }
+
+    // Occurrences from the stored next_run up to now, listing stops past the catch-up limit
+    fn missed_runs(&self, job: &Job, now: DateTime<Utc>) -> Vec<DateTime<Utc>> {
+        let mut missed = vec![];
+        let mut run_time = job.next_run;
+
+        while let Some(t) = run_time && t <= now && missed.len() <= job.max_catchup as usize {
+            missed.push(t);
+            run_time = schedule::next_occurrence(job, t);
+        }
+
+        missed
     }
 
     pub fn queue_size(&self) -> usize {
-        self.jobs.len()
+        self.jobs().jobs.len()
+    }
+
+    // Calendar Functions
+
+    pub fn put_calendar(&self, calendar: Calendar) {
+        let mut calendars = self.calendars_mut();
+
+        match self.store.upsert_calendar(&calendar) {
+            Ok(_) => {},
+            Err(err) => {log::error!("DB Error: Failed to save calendar: {}\n Error output: {:?}", calendar.name, err)}
+        }
+
+        calendars.insert(calendar.name.clone(), calendar);
+    }
+
+    pub fn get_calendar(&self, name: &str) -> Option<Calendar> {
+        self.calendars().get(name).cloned()
+    }
+
+    pub fn get_calendars(&self) -> Vec<Calendar> {
+        let mut calendars: Vec<Calendar> = self.calendars().values().cloned().collect();
+        calendars.sort_by(|a, b| a.name.cmp(&b.name));
+
+        calendars
+    }
+
+    pub fn missing_calendars(&self, names: &[String]) -> Vec<String> {
+        let calendars = self.calendars();
+
+        names.iter().filter(|name| !calendars.contains_key(*name)).cloned().collect()
+    }
+
+    // Calendars still referenced by a schedule that can run again can't be deleted
+    pub fn delete_calendar(&self, name: &str) -> Result<Calendar, CalendarError> {
+        let schedules = self.schedules();
+        let mut calendars = self.calendars_mut();
+
+        if !calendars.contains_key(name) {
+            return Err(CalendarError::NotFound);
+        }
+
+        let used_by: Vec<Uuid> = schedules.schedules.values()
+            .filter(|j| j.status != JobStatus::COMPLETED)
+            .filter(|j| j.calendars.iter().flatten().any(|c| c == name))
+            .map(|j| j.id)
+            .collect();
+
+        if !used_by.is_empty() {
+            return Err(CalendarError::InUse(used_by));
+        }
+
+        match self.store.delete_calendar(name) {
+            Ok(_) => {},
+            Err(err) => {log::error!("DB Error: Failed to delete calendar: {}\n Error output: {:?}", name, err)}
+        }
+
+        calendars.remove(name).ok_or(CalendarError::NotFound)
     }
 
     // Worker Functions
 
     pub fn is_worker_registered(&self, worker_id: Uuid) -> bool {
-        self.workers.contains_key(&worker_id)
+        self.workers().workers.contains_key(&worker_id)
     }
 
-    pub fn register_worker(&mut self, info: WorkerInfo) {
+    pub fn register_worker(&self, info: WorkerInfo) {
         metrics::ACTIVE_WORKERS.inc();
 
-        self.workers.insert(info.worker_id, info.clone());
+        self.workers_mut().workers.insert(info.worker_id, info.clone());
     }
 
-    pub fn update_worker_heartbeat(&mut self, heartbeat: WorkerHeartbeat) {
-        if let Some(worker) = self.workers.get_mut(&heartbeat.worker_id) {
-            worker.last_seen = heartbeat.timestamp;
+    // Returns the ids of running jobs the worker should kill, either canceled or no longer leased to it
+    pub fn update_worker_heartbeat(&self, heartbeat: WorkerHeartbeat) -> Vec<Uuid> {
+        let mut cancel_jobs = {
+            let mut registry = self.workers_mut();
+
+            if let Some(worker) = registry.workers.get_mut(&heartbeat.worker_id) {
+                worker.last_seen = heartbeat.timestamp;
+
+                if worker.status == WorkerStatus::DEAD {
+                    metrics::ACTIVE_WORKERS.inc();
+
+                    worker.status = WorkerStatus::ALIVE
+                }
+            }
+
+            registry.cancel_requests.remove(&heartbeat.worker_id).unwrap_or_default()
+        };
+
+        let mut renewed = vec![];
+
+        {
+            let mut index = self.jobs_mut();
+
+            for lease in heartbeat.leases {
+                if self.renew_lease(&mut index, lease.job_id, lease.lease_token) {
+                    renewed.push(lease.job_id);
+                } else if !cancel_jobs.contains(&lease.job_id) {
+                    log::warn!("Worker {} holds a stale lease on job id: {}, telling it to stop", heartbeat.worker_id, lease.job_id);
+                    cancel_jobs.push(lease.job_id);
+                }
+            }
+        }
+
+        // A worker that re-registered after a restart gets its running jobs back
+        if !renewed.is_empty() && let Some(worker) = self.workers_mut().workers.get_mut(&heartbeat.worker_id) {
+            worker.current_job_ids.extend(renewed);
+        }
+
+        cancel_jobs
+    }
 
-            if worker.status == WorkerStatus::DEAD {
-                metrics::ACTIVE_WORKERS.inc();
+    fn renew_lease(&self, index: &mut JobIndex, job_id: Uuid, token: u64) -> bool {
+        if let Some(job) = index.jobs.get_mut(&job_id)
+            && job.status == JobStatus::RUNNING 
+            && job.lease_token == Some(token) 
+        {
+            let expires_at = Utc::now() + Duration::seconds(LEASE_DURATION);
+            job.lease_expires_at = Some(expires_at);
 
-                worker.status = WorkerStatus::ALIVE
+            match self.store.update_job_lease(job_id, token, expires_at) {
+                Ok(_) => {},
+                Err(err) => {log::error!("DB Error: Failed to renew lease for job id: {}\n Error output: {:?}", job_id, err)}   
             }
+
+            true
+        } else {
+            false
         }
     }
 
-    pub fn check_worker(&mut self) {
-        let dead_workers: Vec<_> = self.workers.iter()
-            .filter_map(|(id, info)| {
-                let last_beat = Utc::now() - info.last_seen;
+    // Clears the job from whichever worker was running it so dead worker recovery won't re-queue it
+    pub fn release_worker_job(&self, job_id: Uuid) {
+        for worker in self.workers_mut().workers.values_mut() {
+            worker.current_job_ids.remove(&job_id);
+        }
+    }
 
-                if last_beat > Duration::seconds(60) && info.status == WorkerStatus::ALIVE {
-                    Some((*id, info.current_job_id))
-                } else {
-                    None
-                }
-            })
-            .collect();
+    pub fn check_worker(&self) {
+        // Marked dead under the same lock they're found with, a heartbeat coming in after brings them back
+        let dead_workers: Vec<_> = {
+            let mut registry = self.workers_mut();
+
+            let dead_workers: Vec<_> = registry.workers.iter()
+                .filter_map(|(id, info)| {
+                    let last_beat = Utc::now() - info.last_seen;
+
+                    if last_beat > Duration::seconds(60) && info.status == WorkerStatus::ALIVE {
+                        Some((*id, info.current_job_ids.clone()))
+                    } else {
+                        None
+                    } 
+                })
+                .collect();
 
-        for (worker_id, recovered_job_option) in dead_workers {
-            if let Some(w) = self.workers.get_mut(&worker_id) {
-                w.status = WorkerStatus::DEAD;
-                w.current_job_id = None;
+            for (worker_id, _) in &dead_workers {
+                if let Some(w) = registry.workers.get_mut(worker_id) {
+                    w.status = WorkerStatus::DEAD;
+                    w.current_job_ids.clear();
+                }
             }
 
+            dead_workers
+        };
+
+        for (worker_id, recovered_jobs) in dead_workers {
             metrics::ACTIVE_WORKERS.dec();
 
-            if let Some(job_id) = recovered_job_option {
-                if let Some(j) = self.get_job(job_id) {
-                    metrics::QUEUE_DEPTH.with_label_values(&[&j.priority.to_string()]).inc();
+            if recovered_jobs.is_empty() {
+                log::warn!("Worker {} heartbeat too old, marking as dead", worker_id);
+            }
 
-                    match j.priority {
-                        Priority::HIGH => self.pending_high.push_back(j.clone()),
-                        Priority::MEDIUM => self.pending_medium.push_back(j.clone()),
-                        Priority::LOW => self.pending_low.push_back(j.clone()),
-                    }
-                    self.update_job_status(j.id, JobStatus::PENDING);
-                    
+            for job_id in recovered_jobs {
+                if self.requeue_running_job(job_id) {
                     log::warn!("Worker {} is dead, recovered job id: {}", worker_id, job_id);
                 }
-            } else {
-                log::warn!("Worker {} heartbeat too old, marking as dead", worker_id);
+            }
+        }
+
+        let expired_leases: Vec<Uuid> = self.jobs().jobs.values()
+            .filter(|j| j.status == JobStatus::RUNNING && j.lease_expires_at.is_some_and(|t| t < Utc::now()))
+            .map(|j| j.id)
+            .collect();
+
+        for job_id in expired_leases {
+            self.release_worker_job(job_id);
+
+            if self.requeue_running_job(job_id) {
+                log::warn!("Lease on job id: {} expired, re-queued it", job_id);
             }
         }
     }
 
+    // Puts a job that was lost with its worker back in the queue, its next claim gets a new lease token
+    fn requeue_running_job(&self, job_id: Uuid) -> bool {
+        let requeued = {
+            let mut index = self.jobs_mut();
+
+            match index.jobs.get(&job_id).cloned() {
+                Some(j) if j.status == JobStatus::RUNNING => {
+                    self.update_job_status(&mut index, j.id, JobStatus::PENDING);
+                    Some(j)
+                },
+                _ => None
+            }
+        };
+
+        match requeued {
+            Some(j) => {
+                metrics::QUEUE_DEPTH.with_label_values(&[&j.priority.to_string()]).inc();
+
+                self.ready().push(j);
+
+                true
+            },
+            None => false
+        }
+    }
+
     // Job Functions
-    
-    pub fn add_scheduled_jobs(&mut self, job: Job) {
-        match db::insert_job(&self.connection, job.clone()) {
+
+    pub fn add_scheduled_jobs(&self, job: Job) {
+        match self.store.insert_job(job.clone()) {
             Ok(_) => {},
             Err(err) => {log::error!("DB Error: Failed to insert job into the database for job id: {}\n Error output: {:?}", job.id, err)}
         }
 
-        self.schedules.insert(job.id, job.clone());
+        self.set_schedule(&mut self.schedules_mut(), job);
     }
 
-    pub fn check_scheduled_jobs(&mut self) {
-        log::info!("Checking {} scheduled jobs", self.schedules.len());
-        for (job_id, jobs) in self.schedules.clone() {
-            if let Some(next_run_time) = jobs.next_run {
-                let run_time = next_run_time - Utc::now();
+    pub fn update_schedule(&self, job_id: Uuid, update: UpdateScheduleRequest) -> Result<Job, ScheduleError> {
+        let mut schedules = self.schedules_mut();
+        let mut job = Self::active_schedule(&schedules, job_id)?.clone();
+
+        match (update.schedule, update.every) {
+            (Some(_), Some(_)) => return Err(ScheduleError::InvalidSchedule),
+            (Some(expr), None) => {
+                job.schedule = Some(schedule::normalize_cron(&expr).ok_or(ScheduleError::InvalidSchedule)?);
+                job.interval_secs = None;
+                job.anchor = None;
+                job.next_run = schedule::next_occurrence(&job, Utc::now());
+            },
+            // Switching from a cron starts the intervals from now, otherwise they keep their anchor
+            (None, Some(every)) => {
+                job.interval_secs = Some(schedule::parse_interval(&every).ok_or(ScheduleError::InvalidSchedule)?);
+                job.anchor = job.anchor.or_else(|| Some(Utc::now()));
+                job.schedule = None;
+                job.next_run = schedule::next_occurrence(&job, Utc::now());
+            },
+            (None, None) => {}
+        }
+
+        if let Some(command) = update.command {
+            job.command = command;
+        }
+
+        if let Some(args) = update.args {
+            job.args = args;
+        }
 
-                log::info!("Old run time {}", run_time);
+        if let Some(priority) = update.priority {
+            job.priority = priority;
+        }
 
-                // +/- 30 seconds window, so ~60 second window
-                if Duration::seconds(-30) <= run_time && run_time <= Duration::seconds(30) {
-                    let sched_job = Job {
-                        id: Uuid::new_v4(),
-                        command: jobs.command,
-                        args: jobs.args,
-                        status: jobs.status,
-                        timestamp: Utc::now(),
-                        
-                        retry_count: 0,
-                        max_retries: jobs.max_retries,
+        match self.store.update_schedule(&job) {
+            Ok(_) => {},
+            Err(err) => {log::error!("DB Error: Failed to update schedule for job id: {}\n Error output: {:?}", job_id, err)}
+        }
 
-                        priority: jobs.priority,
+        if job.next_run.is_none() && job.status == JobStatus::PENDING {
+            self.finish_schedule(&mut schedules, job.clone());
+            job.status = JobStatus::COMPLETED;
+        } else {
+            self.set_schedule(&mut schedules, job.clone());
+        }
+
+        Ok(job)
+    }
+
+    // A paused schedule keeps its timer entry, it's skipped when it comes up
+    pub fn pause_schedule(&self, job_id: Uuid) -> Result<Job, ScheduleError> {
+        let mut schedules = self.schedules_mut();
+
+        Self::active_schedule(&schedules, job_id)?;
+        let job = schedules.schedules.get_mut(&job_id).ok_or(ScheduleError::NotFound)?;
+
+        match self.store.update_job_status(job_id, JobStatus::PAUSED) {
+            Ok(_) => {},
+            Err(err) => {log::error!("DB Error: Failed update status for job id: {}\n Error output: {:?}", job_id, err)}   
+        }
+
+        job.status = JobStatus::PAUSED;
+
+        Ok(job.clone())
+    }
+
+    // Runs that came up while paused are skipped, the schedule carries on from now
+    pub fn resume_schedule(&self, job_id: Uuid) -> Result<Job, ScheduleError> {
+        let mut schedules = self.schedules_mut();
+        let mut job = Self::active_schedule(&schedules, job_id)?.clone();
+
+        if job.status != JobStatus::PAUSED {
+            return Ok(job);
+        }
 
-                        parent_schedule_id: Some(job_id),
+        job.status = JobStatus::PENDING;
+        job.next_run = schedule::next_occurrence(&job, Utc::now());
 
-                        schedule: None,
-                        next_run: None,
-                        is_recurring: false,
+        if job.next_run.is_none() {
+            self.finish_schedule(&mut schedules, job.clone());
+            job.status = JobStatus::COMPLETED;
+            return Ok(job);
+        }
+
+        match self.store.update_job_status(job_id, JobStatus::PENDING) {
+            Ok(_) => {},
+            Err(err) => {log::error!("DB Error: Failed update status for job id: {}\n Error output: {:?}", job_id, err)}   
+        }
+
+        if let Some(next_time) = job.next_run {
+            match self.store.update_schedule_run(job_id, next_time) {
+                Ok(_) => {},
+                Err(err) => {log::error!("DB Error: Failed update schedule time for job id: {}\n Error output: {:?}", job_id, err)}   
+            }
+        }
+
+        self.set_schedule(&mut schedules, job.clone());
+
+        Ok(job)
+    }
+
+    // The row is kept as CANCELED so the runs it already started still point at something
+    pub fn delete_schedule(&self, job_id: Uuid) -> Result<Job, ScheduleError> {
+        let mut schedules = self.schedules_mut();
+        let mut job = schedules.schedules.remove(&job_id).ok_or(ScheduleError::NotFound)?;
+
+        schedules.backfills.remove(&job_id);
+
+        match self.store.update_job_status(job_id, JobStatus::CANCELED) {
+            Ok(_) => {},
+            Err(err) => {log::error!("DB Error: Failed update status for job id: {}\n Error output: {:?}", job_id, err)}   
+        }
+
+        job.status = JobStatus::CANCELED;
+
+        Ok(job)
+    }
+
+    // Completed schedules are kept around for lookups but can't be changed
+    fn active_schedule(schedules: &Schedules, job_id: Uuid) -> Result<&Job, ScheduleError> {
+        match schedules.schedules.get(&job_id) {
+            Some(job) if job.status == JobStatus::COMPLETED => Err(ScheduleError::Finished),
+            Some(job) => Ok(job),
+            None => Err(ScheduleError::NotFound)
+        }
+    }
+
+    fn set_schedule(&self, schedules: &mut Schedules, job: Job) {
+        if let Some(next_time) = job.next_run {
+            schedules.timers.push(Reverse((next_time, job.id)));
+        }
+
+        schedules.schedules.insert(job.id, job);
+
+        // The schedule may now be due before whatever the scheduler loop is sleeping on
+        self.schedule_wakeup.notify_one();
+    }
+
+    // Deleted schedules are only in the database but their runs can still be looked up
+    pub fn get_schedule_runs(&self, job_id: Uuid, limit: usize) -> Option<Vec<ScheduleRun>> {
+        let in_memory = self.schedules().schedules.contains_key(&job_id);
+        let exists = in_memory || matches!(self.store.get_job(job_id), Ok(Some(job)) if job.is_recurring);
+
+        if !exists {
+            return None;
+        }
 
-                        depends_on: None // Might just put the parent ID here as it "depends" on the parent to be running but the parent isn't required for it or smth
+        match self.store.get_schedule_runs(job_id, limit) {
+            Ok(runs) => Some(runs),
+            Err(err) => {
+                log::error!("DB Error: Failed to load runs for schedule id: {}\n Error output: {:?}", job_id, err);
+                Some(vec![])
+            }
+        }
+    }
+
+    // Upcoming fire times from now, a paused schedule shows what it would do once resumed
+    pub fn preview_schedule(&self, job_id: Uuid, n: usize) -> Option<Vec<DateTime<Utc>>> {
+        let job = self.schedules().schedules.get(&job_id).cloned()?;
+
+        if job.status == JobStatus::COMPLETED {
+            return Some(vec![]);
+        }
+
+        let n = schedule::runs_left(&job).map_or(n, |left| n.min(left as usize));
+
+        let mut next_runs = vec![];
+        let mut after = Utc::now();
+
+        while next_runs.len() < n && let Some(next_time) = schedule::next_occurrence(&job, after) {
+            next_runs.push(next_time);
+            after = next_time;
+        }
+
+        Some(next_runs)
+    }
+
+    // Queues a run for every occurrence in [from, to], they're started by `start_backfill_runs` a few at a time
+    pub fn backfill_schedule(&self, job_id: Uuid, from: DateTime<Utc>, to: DateTime<Utc>, max_in_flight: usize) -> Result<Vec<DateTime<Utc>>, ScheduleError> {
+        let mut schedules = self.schedules_mut();
+        let job = schedules.schedules.get(&job_id).ok_or(ScheduleError::NotFound)?;
+
+        let runs = schedule::occurrences_between(job, from, to, MAX_BACKFILL_RUNS + 1);
+
+        if runs.len() > MAX_BACKFILL_RUNS {
+            return Err(ScheduleError::TooManyRuns);
+        }
+
+        let backfill = schedules.backfills.entry(job_id).or_insert_with(|| Backfill {
+            pending: VecDeque::new(), 
+            in_flight: HashSet::new(), 
+            max_in_flight 
+        });
+
+        backfill.max_in_flight = max_in_flight;
+        backfill.pending.extend(runs.iter().copied());
+
+        log::info!("Backfilling schedule {} with {} run(s) between {} and {}", job_id, runs.len(), from, to);
+
+        Ok(runs)
+    }
+
+    pub fn start_backfill_runs(&self) {
+        let mut guard = self.schedules_mut();
+        let schedules = &mut *guard;
+        let mut due = vec![];
+
+        {
+            let index = self.jobs();
+
+            for (schedule_id, backfill) in schedules.backfills.iter_mut() {
+                backfill.in_flight.retain(|id| index.jobs.get(id)
+                    .is_some_and(|j| matches!(j.status, JobStatus::PENDING | JobStatus::RUNNING | JobStatus::RETRYING | JobStatus::WAITING | JobStatus::SCHEDULED)));
+
+                while backfill.in_flight.len() < backfill.max_in_flight && let Some(run_time) = backfill.pending.pop_front() {
+                    let Some(parent) = schedules.schedules.get(schedule_id) else {
+                        break;
                     };
 
+                    let mut job = Self::schedule_child(parent, run_time, None);
+                    job.env = Some(HashMap::from([(String::from("SCHEDULER_BACKFILL"), String::from("1"))]));
 
-                    self.add_job(sched_job);
+                    backfill.in_flight.insert(job.id);
+                    due.push((job, run_time));
                 }
+            }
+        }
 
-                if jobs.is_recurring {
-                    if let Some(j) = self.schedules.get_mut(&job_id) {
-                        let next;
-                        
-                        if let Some(sched) = &j.schedule.clone() { 
-                            next = Schedule::from_str(sched)
-                                .ok()
-                                .and_then(|s| s.upcoming(Utc).next());
-                            
-                            if let Some(next_time) = next {
-                                log::info!("New run time {}", next_time);
-
-                                match db::update_schedule_run(&self.connection, job_id, next_time) {
-                                    Ok(_) => {},
-                                    Err(err) => {log::error!("DB Error: Failed update schedule time for job id: {}\n Error output: {:?}", j.id, err)}   
-                                }
-
-                                j.next_run = Some(next_time);
-                            }
-                        }
-                    }
+        for (job, run_time) in due {
+            log::info!("Starting backfill run {} of schedule {:?} for {}", job.id, job.parent_schedule_id, run_time);
+            self.add_job(job);
+        }
+
+        schedules.backfills.retain(|schedule_id, backfill| {
+            let done = backfill.pending.is_empty() && backfill.in_flight.is_empty();
+            if done {
+                log::info!("Backfill of schedule {} finished", schedule_id);
+            }
+
+            !done
+        });
+    }
+
+    pub fn schedule_notifier(&self) -> Arc<Notify> {
+        self.schedule_wakeup.clone()
+    }
+
+    // Fires every schedule occurrence due by `now`, returns when the next one is due
+    pub fn fire_due_schedules(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut schedules = self.schedules_mut();
+
+        while let Some(Reverse((run_time, job_id))) = schedules.timers.peek().copied() {
+            if run_time > now {
+                return Some(run_time);
+            }
+
+            schedules.timers.pop();
+
+            let mut parent = match schedules.schedules.get(&job_id) {
+                Some(j) if j.next_run == Some(run_time) && j.status == JobStatus::PENDING => j.clone(),
+                _ => continue
+            };
+
+            log::info!("Schedule {} is due, run time {}", job_id, run_time);
+
+            self.run_schedule(&mut parent, run_time, schedules.backfills.get(&job_id).map(|backfill| &backfill.in_flight));
+
+            // Counted from the occurrence that just fired so none are skipped or repeated
+            parent.next_run = schedule::next_occurrence(&parent, run_time);
+
+            match parent.next_run {
+                Some(next_time) => {
+                    log::info!("New run time {}", next_time);
+
+                    match self.store.update_schedule_run(job_id, next_time) {
+                        Ok(_) => {},
+                        Err(err) => {log::error!("DB Error: Failed update schedule time for job id: {}\n Error output: {:?}", job_id, err)}   
+                    } 
+
+                    schedules.timers.push(Reverse((next_time, job_id)));
+                    schedules.schedules.insert(job_id, parent);
+                },
+                None => self.finish_schedule(&mut schedules, parent)
+            }
+        }
+
+        None
+    }
+
+    // Starts the run due at `run_time` and counts it towards the schedule's max_runs,
+    // unless it falls in one of the schedule's blackouts. `backfill_runs` are the schedule's backfill runs still going
+    fn run_schedule(&self, parent: &mut Job, run_time: DateTime<Utc>, backfill_runs: Option<&HashSet<Uuid>>) {
+        let run_at = {
+            let calendars = self.calendars();
+            let calendars: Vec<&Calendar> = parent.calendars.iter().flatten()
+                .filter_map(|name| calendars.get(name))
+                .collect();
+
+            match schedule::blackout_end(&calendars, run_time) {
+                Some(end) if parent.blackout_policy == BlackoutPolicy::SKIP => {
+                    log::info!("Skipping run of schedule {} at {} as it falls in a blackout until {}", parent.id, run_time, end);
+                    return;
+                },
+                // Every occurrence inside one blackout collapses into the single run held for its end
+                Some(end) if self.jobs().jobs.values().any(|j| j.parent_schedule_id == Some(parent.id) && j.status == JobStatus::SCHEDULED && j.run_at >= Some(end)) => {
+                    log::info!("Skipping run of schedule {} at {} as a run is already deferred past {}", parent.id, run_time, end);
+                    return;
+                },
+                Some(end) => {
+                    log::info!("Deferring run of schedule {} at {} to the end of its blackout at {}", parent.id, run_time, end);
+                    Some(end)
+                },
+                None => None
+            }
+        };
+
+        let run_at = match parent.jitter_secs {
+            Some(jitter) if jitter > 0 => {
+                let jitter = Duration::seconds(rand::random_range(0..=jitter.min(schedule::MAX_JITTER_SECS) as i64));
+                run_at.unwrap_or(run_time).checked_add_signed(jitter).or(run_at)
+            },
+            _ => run_at
+        };
+
+        if self.start_schedule_run(parent, run_time, run_at, backfill_runs) {
+            parent.run_count += 1;
+
+            match self.store.update_run_count(parent.id, parent.run_count) {
+                Ok(_) => {},
+                Err(err) => {log::error!("DB Error: Failed update run count for job id: {}\n Error output: {:?}", parent.id, err)}   
+            }
+        }
+    }
+
+    // Past not_after or out of runs, it stays in memory as COMPLETED so its status can still be looked up
+    fn finish_schedule(&self, schedules: &mut Schedules, mut job: Job) {
+        log::info!("Schedule {} has no runs left after {} run(s), marking it completed", job.id, job.run_count);
+
+        match self.store.update_job_status(job.id, JobStatus::COMPLETED) {
+            Ok(_) => {},
+            Err(err) => {log::error!("DB Error: Failed update status for job id: {}\n Error output: {:?}", job.id, err)}   
+        }
+
+        job.status = JobStatus::COMPLETED;
+        job.next_run = None;
+
+        schedules.schedules.insert(job.id, job);
+    }
+
+    // Applies the schedule's concurrency policy against runs it started that haven't finished yet,
+    // returns false if the policy skipped this run. Backfill runs are left out, they have their own limit
+    fn start_schedule_run(&self, parent: &Job, run_time: DateTime<Utc>, run_at: Option<DateTime<Utc>>, backfill_runs: Option<&HashSet<Uuid>>) -> bool {
+        let active_runs: Vec<Uuid> = self.jobs().jobs.values()
+            .filter(|j| j.parent_schedule_id == Some(parent.id))
+            .filter(|j| !backfill_runs.is_some_and(|runs| runs.contains(&j.id)))
+            .filter(|j| matches!(j.status, JobStatus::PENDING | JobStatus::RUNNING | JobStatus::RETRYING | JobStatus::WAITING | JobStatus::SCHEDULED))
+            .map(|j| j.id)
+            .collect();
+
+        if !active_runs.is_empty() {
+            match parent.concurrency_policy {
+                ConcurrencyPolicy::ALLOW => {},
+                ConcurrencyPolicy::FORBID => {
+                    log::info!("Skipping run of schedule {} as {} previous run(s) haven't finished", parent.id, active_runs.len());
+                    return false;
+                },
+                ConcurrencyPolicy::REPLACE => {
+                    for job_id in active_runs {
+                        log::info!("Canceling job id: {} to replace it with a new run of schedule {}", job_id, parent.id);
+                        let _ = self.cancel_job(job_id);
+                    } 
                 }
             }
         }
+
+        self.spawn_schedule_run(parent, run_time, run_at);
+
+        true
     }
 
-    pub fn add_job(&mut self, job: Job) {
-        match db::insert_job(&self.connection, job.clone()) {
+    // A run_at holds the run as SCHEDULED until then
+    fn spawn_schedule_run(&self, parent: &Job, run_time: DateTime<Utc>, run_at: Option<DateTime<Utc>>) {
+        let sched_job = Self::schedule_child(parent, run_time, run_at);
+
+        if run_at.is_some() {
+            self.add_delayed_job(sched_job);
+        } else {
+            self.add_job(sched_job);
+        }
+    }
+
+    fn schedule_child(parent: &Job, run_time: DateTime<Utc>, run_at: Option<DateTime<Utc>>) -> Job {
+        Job {
+            id: Uuid::new_v4(),
+            command: parent.command.clone(),
+            args: parent.args.clone(),
+            status: if run_at.is_some() { JobStatus::SCHEDULED } else { JobStatus::PENDING },
+            timestamp: Utc::now(),
+            
+            retry_count: 0,
+            max_retries: parent.max_retries,
+            retry_policy: parent.retry_policy.clone(),
+            next_attempt_at: None,
+
+            priority: parent.priority.clone(),
+
+            parent_schedule_id: Some(parent.id),
+            scheduled_for: Some(run_time),
+
+            schedule: None,
+            interval_secs: None,
+            anchor: None,
+            jitter_secs: None,
+            timezone: None,
+            misfire_policy: MisfirePolicy::default(),
+            max_catchup: 0,
+            concurrency_policy: ConcurrencyPolicy::default(),
+            not_before: None,
+            not_after: None,
+            max_runs: None,
+            run_count: 0,
+            calendars: None,
+            blackout_policy: BlackoutPolicy::default(),
+            next_run: None,
+            is_recurring: false,
+
+            run_at,
+
+            depends_on: None, // Might just put the parent ID here as it "depends" on the parent to be running but the parent isn't required for it or smth
+
+            timeout_secs: parent.timeout_secs,
+
+            env: None,
+
+            lease_token: None,
+            lease_expires_at: None
+        }
+    }
+
+    // In the index before its copy is queued, so a worker can't be handed a job the index doesn't know yet
+    pub fn add_job(&self, job: Job) {
+        match self.store.insert_job(job.clone()) {
             Ok(_) => {},
             Err(err) => {log::error!("DB Error: Failed insert job into database. Job id: {}\n Error output: {:?}", job.id, err)}   
         }
-        
-        self.jobs.insert(job.id, job.clone());
+
+        self.jobs_mut().jobs.insert(job.id, job.clone());
 
         metrics::JOBS_SUBMITTED_TOTAL.with_label_values(&[&job.priority.to_string()]).inc();
         metrics::QUEUE_DEPTH.with_label_values(&[&job.priority.to_string()]).inc();
-        
-        match job.priority {
-            Priority::HIGH => self.pending_high.push_back(job),
-            Priority::MEDIUM => self.pending_medium.push_back(job),
-            Priority::LOW => self.pending_low.push_back(job),
+
+        self.ready().push(job);
+    }
+
+    // Held back until `run_at`, `promote_delayed_jobs` moves it into the pending queue once it's due
+    pub fn add_delayed_job(&self, job: Job) {
+        match self.store.insert_job(job.clone()) {
+            Ok(_) => {},
+            Err(err) => {log::error!("DB Error: Failed insert job into database. Job id: {}\n Error output: {:?}", job.id, err)}   
         }
+
+        metrics::JOBS_SUBMITTED_TOTAL.with_label_values(&[&job.priority.to_string()]).inc();
+
+        let due = (job.run_at.unwrap_or_else(Utc::now), job.id);
+
+        self.jobs_mut().jobs.insert(job.id, job);
+        self.ready().delayed.insert(due);
     }
 
-    fn add_worker_job(&mut self, j: Job, requester: Uuid) {
-        self.update_job_status(j.id, JobStatus::RUNNING);
-        
-        if let Some(worker) = self.workers.get_mut(&requester) {
-            worker.current_job_id = Some(j.id);
+    pub fn promote_delayed_jobs(&self) {
+        let now = Utc::now();
+        let mut due = vec![];
+
+        {
+            let mut ready = self.ready();
+
+            while let Some((run_at, job_id)) = ready.delayed.first().copied() && run_at <= now {
+                ready.delayed.pop_first();
+                due.push(job_id);
+            }
         }
 
-        match db::update_job_status(&self.connection, j.id, JobStatus::RUNNING) {
-            Ok(_) => {},
-            Err(err) => {log::error!("DB Error: Failed to set job status to running for job id: {}\n Error output: {:?}", j.id, err)}   
-        }
-    }
-
-    pub fn get_next_job(&mut self, requester: Uuid) -> Option<Job> {
-        let job = self.pending_high.pop_front()
-            .or_else(|| self.pending_medium.pop_front())
-            .or_else(|| self.pending_low.pop_front());
-        
-        if let Some(j) = job.clone() {
-            if let Some(requirements) = j.clone().depends_on && (j.depends_on.iter().len() > 0) {
-                let mut completed = vec![];
-                let mut failed_req = false;
-                
-                for id in requirements.iter() {
-                    let checking = self.get_job(*id);
-                    match checking {
-                        Some(job) => {
-                            if job.status == JobStatus::COMPLETED {
-                                completed.push(id);
-                            }
-                            if job.status == JobStatus::FAILED || job.status == JobStatus::CANCELED {
-                                failed_req = true;
-                            }
-                        },
-                        None => {} // Do nothing so the job gets re-queued as failed to get required job
-                    } 
+        for job_id in due {
+            let promoted = {
+                let mut index = self.jobs_mut();
+
+                match index.jobs.get(&job_id).cloned() {
+                    Some(j) if j.status == JobStatus::SCHEDULED => {
+                        self.update_job_status(&mut index, job_id, JobStatus::PENDING);
+                        Some(j)
+                    },
+                    _ => None
                 }
+            };
 
-                if completed.len() == requirements.len() {
-                    if j.status == JobStatus::WAITING {
-                        metrics::JOBS_WAITING_TOTAL.dec();
-                    }
-                    metrics::QUEUE_DEPTH.with_label_values(&[&j.priority.to_string()]).dec();
+            if let Some(j) = promoted {
+                metrics::QUEUE_DEPTH.with_label_values(&[&j.priority.to_string()]).inc();
 
-                    self.add_worker_job(j.clone(), requester);
-                    return Some(j);
-                } else {
-                    // Add job back into the VecDeque without re-adding it to the DB
-                    // As long if one of the required jobs hasn't failed or been canceled
-                    if failed_req {
-                        metrics::QUEUE_DEPTH.with_label_values(&[&j.priority.to_string()]).dec();
+                self.ready().push(j);
 
-                        self.update_job_status(j.id, JobStatus::FAILED);
-                    } else {
-                        if j.status != JobStatus::WAITING {
-                            metrics::JOBS_WAITING_TOTAL.inc();
+                log::info!("Delayed job id: {} is due, moved it to the pending queue", job_id);
+            }
+        }
+    }
 
-                            self.update_job_status(j.id, JobStatus::WAITING);
-                        }
+    // Hands the job to the worker under a fresh lease, the returned job carries the token the worker has to present
+    fn add_worker_job(&self, index: &mut JobIndex, job_id: Uuid) -> Option<Job> {
+        self.update_job_status(index, job_id, JobStatus::RUNNING);
 
-                        match j.priority {
-                            Priority::HIGH => self.pending_high.push_back(j.clone()),
-                            Priority::MEDIUM => self.pending_medium.push_back(j.clone()),
-                            Priority::LOW => self.pending_low.push_back(j.clone()),
-                        };
-                    }
+        index.next_lease_token += 1;
+        let token = index.next_lease_token;
+        let expires_at = Utc::now() + Duration::seconds(LEASE_DURATION);
 
-                    return None;
-                }
+        match self.store.update_job_lease(job_id, token, expires_at) {
+            Ok(_) => {},
+            Err(err) => {log::error!("DB Error: Failed to store lease for job id: {}\n Error output: {:?}", job_id, err)}
+        }
 
-            } else {
+        let job = index.jobs.get_mut(&job_id)?;
+        job.lease_token = Some(token);
+        job.lease_expires_at = Some(expires_at);
+
+        Some(job.clone())
+    }
+
+    // Dependencies are looked up before the index is locked for writing, a finished one may only be in the store
+    pub fn get_next_job(&self, requester: Uuid) -> Option<Job> {
+        if let Some(worker) = self.workers().workers.get(&requester) && worker.current_job_ids.len() >= worker.capacity {
+            log::info!("Worker {} has no free slots, not handing out a job", requester);
+            return None;
+        }
+
+        let j = self.ready().pop_ready()?;
+
+        let mut completed = vec![];
+        let mut failed_req = false;
+
+        for id in j.depends_on.iter().flatten() {
+            match self.dependency_status(*id) {
+                Some(JobStatus::COMPLETED) => completed.push(id),
+                Some(JobStatus::FAILED | JobStatus::CANCELED) => failed_req = true,
+                _ => {} // Do nothing so the job gets re-queued as failed to get required job
+            }
+        }
+
+        let mut index = self.jobs_mut();
+
+        // The copy may be from before the job was canceled
+        let status = match index.jobs.get(&j.id) {
+            Some(job) if matches!(job.status, JobStatus::PENDING | JobStatus::RETRYING | JobStatus::WAITING) => job.status.clone(),
+            _ => {
                 metrics::QUEUE_DEPTH.with_label_values(&[&j.priority.to_string()]).dec();
+                return None;
+            }
+        };
 
-                self.add_worker_job(j.clone(), requester);
-                return Some(j)
+        if completed.len() == j.depends_on.as_ref().map_or(0, Vec::len) {
+            if status == JobStatus::WAITING {
+                metrics::JOBS_WAITING_TOTAL.dec();
             }
+            metrics::QUEUE_DEPTH.with_label_values(&[&j.priority.to_string()]).dec();
+
+            let job = self.add_worker_job(&mut index, j.id);
+            drop(index);
+
+            if job.is_some() && let Some(worker) = self.workers_mut().workers.get_mut(&requester) {
+                worker.current_job_ids.insert(j.id);
+            }
+
+            job
+        } else if failed_req {
+            metrics::QUEUE_DEPTH.with_label_values(&[&j.priority.to_string()]).dec();
+
+            self.update_job_status(&mut index, j.id, JobStatus::FAILED);
+
+            None
         } else {
-            return None
+            // Add job back into the VecDeque without re-adding it to the DB
+            // As long if one of the required jobs hasn't failed or been canceled
+            if status != JobStatus::WAITING {
+                metrics::JOBS_WAITING_TOTAL.inc();
+
+                self.update_job_status(&mut index, j.id, JobStatus::WAITING);
+            }
+            drop(index);
+
+            self.ready().push(j);
+
+            None
         }
     }
 
+    // Falls back to the database for finished jobs, which are evicted from memory and aren't reloaded after a restart
     pub fn get_job(&self, job_id: Uuid) -> Option<Job> {
-        self.jobs.get(&job_id).cloned().or_else(|| self.schedules.get(&job_id).cloned())
+        let job = self.jobs().jobs.get(&job_id).cloned();
+
+        job.or_else(|| self.schedules().schedules.get(&job_id).cloned())
+            .or_else(|| self.store.get_job(job_id).unwrap_or_else(|err| {
+                log::error!("DB Error: Failed to look up job id: {}\n Error output: {:?}", job_id, err);
+                None
+            }))
+    }
+
+    // A dependency that finished before the last restart or was evicted a while ago is only in the store,
+    // its status is kept with the recently evicted ones after the lookup
+    fn dependency_status(&self, job_id: Uuid) -> Option<JobStatus> {
+        {
+            let index = self.jobs();
+
+            if let Some(status) = index.jobs.get(&job_id).map(|job| &job.status).or_else(|| index.evicted_status(job_id)) {
+                return Some(status.clone());
+            }
+        }
+
+        let job = self.get_job(job_id)?;
+
+        if !job.is_recurring && matches!(job.status, JobStatus::COMPLETED | JobStatus::FAILED | JobStatus::CANCELED) {
+            let mut index = self.jobs_mut();
+
+            if !index.evicted.contains_key(&job_id) {
+                index.remember_evicted(job_id, job.status.clone(), Utc::now());
+            }
+        }
+
+        Some(job.status)
     }
 
     pub fn get_job_status(&self, job_id: Uuid) -> Option<GetJobStatusResponse> {
+        let job = self.get_job(job_id)?;
+
+        let attempts = self.store.get_attempts(job_id).unwrap_or_else(|err| {
+            log::error!("DB Error: Failed to load attempts for job id: {}\n Error output: {:?}", job_id, err);
+            vec![]
+        });
+
+        let result = self.jobs().results.get(&job_id).cloned();
+
         Some(GetJobStatusResponse {
-            job: self.get_job(job_id)?,
-            result: self.results.get(&job_id).cloned()
+            job,
+            result: result.or_else(|| self.store.get_result(job_id).unwrap_or_else(|err| {
+                log::error!("DB Error: Failed to load results for job id: {}\n Error output: {:?}", job_id, err);
+                None
+            })),
+            attempts
         })
     }
 
-    pub fn get_list(&self, status: Option<JobStatus>) -> Result<Vec<Job>, rusqlite::Error> {
-        db::get_job_list(&self.connection, status)
+    pub fn get_list(&self, status: Option<JobStatus>) -> Result<Vec<Job>, StoreError> {
+        self.store.get_job_list(status)
     }
 
-    pub fn retry_job(&mut self, job_id: Uuid) {
-        if let Some(job) = self.jobs.get_mut(&job_id) {
-            job.status = JobStatus::RETRYING;
-            job.retry_count += 1;
-
-            match db::update_job_status(&self.connection, job_id, JobStatus::RETRYING) {
-                Ok(_) => {},
-                Err(err) => {log::error!("DB Error: Failed update status for job id: {}\n Error output: {:?}", job_id, err)}   
+    // Results are only accepted from the holder of the current lease, anything else is from an attempt that was re-queued.
+    // The lease is checked and the job moved on under one lock so a re-queue or cancel can't land in between.
+    // Returns the job as it was when the result came in
+    pub fn settle_result(&self, job_id: Uuid, token: u64, results: JobResult) -> Option<(Job, ResultOutcome)> {
+        let mut index = self.jobs_mut();
+
+        let job = index.jobs.get(&job_id)
+            .filter(|job| (job.status == JobStatus::RUNNING || job.status == JobStatus::CANCELED) && job.lease_token == Some(token))
+            .cloned()?;
+
+        let outcome = if job.status == JobStatus::CANCELED {
+            ResultOutcome::Canceled
+        } else if results.exitcode != 0 || results.timed_out {
+            if job.retry_count < job.max_retries && (results.timed_out || job.retry_policy.is_retryable(results.exitcode)) {
+                ResultOutcome::Retrying
+            } else if job.retry_count < job.max_retries {
+                ResultOutcome::NotRetryable
+            } else {
+                ResultOutcome::OutOfRetries
             }
-            
-            match db::update_retry_count(&self.connection, job_id, job.retry_count) {
-                Ok(_) => {},
-                Err(err) => {log::error!("DB Error: Failed update retry count for job id: {}\n Error output: {:?}", job_id, err)}   
+        } else {
+            ResultOutcome::Completed
+        };
+
+        match outcome {
+            ResultOutcome::Canceled => self.store_results(&mut index, job_id, results),
+            ResultOutcome::Retrying => {
+                let retried = self.retry_job(&mut index, job_id);
+                drop(index);
+
+                if let Some(j) = retried {
+                    self.ready().push(j);
+                }
+            },
+            ResultOutcome::NotRetryable | ResultOutcome::OutOfRetries => {
+                self.store_results(&mut index, job_id, results);
+                self.update_job_status(&mut index, job_id, JobStatus::FAILED);
+            },
+            ResultOutcome::Completed => {
+                self.store_results(&mut index, job_id, results);
+                self.update_job_status(&mut index, job_id, JobStatus::COMPLETED);
             }
+        }
+
+        Some((job, outcome))
+    }
+
+    // Returns the job to put back in the pending queue
+    fn retry_job(&self, index: &mut JobIndex, job_id: Uuid) -> Option<Job> {
+        let job = index.jobs.get_mut(&job_id)?;
+
+        job.status = JobStatus::RETRYING;
+        job.retry_count += 1;
+
+        match self.store.update_job_status(job_id, JobStatus::RETRYING) {
+            Ok(_) => {},
+            Err(err) => {log::error!("DB Error: Failed update status for job id: {}\n Error output: {:?}", job_id, err)}   
+        }
+
+        match self.store.update_retry_count(job_id, job.retry_count) {
+            Ok(_) => {},
+            Err(err) => {log::error!("DB Error: Failed update retry count for job id: {}\n Error output: {:?}", job_id, err)}   
+        }
+
+        let next_attempt_at = Utc::now().checked_add_signed(backoff_delay(&job.retry_policy, job.retry_count))
+            .unwrap_or_else(|| Utc::now() + Duration::seconds(MAX_RETRY_DELAY_SECS as i64));
+        job.next_attempt_at = Some(next_attempt_at);
 
-            metrics::QUEUE_DEPTH.with_label_values(&[&job.priority.to_string()]).inc();
+        match self.store.update_next_attempt(job_id, next_attempt_at) {
+            Ok(_) => {},
+            Err(err) => {log::error!("DB Error: Failed update next attempt time for job id: {}\n Error output: {:?}", job_id, err)}
+        }
 
-            match job.priority {
-                Priority::HIGH => self.pending_high.push_back(job.clone()),
-                Priority::MEDIUM => self.pending_medium.push_back(job.clone()),
-                Priority::LOW => self.pending_low.push_back(job.clone()),
+        log::info!("Job id: {} will be retried at {}", job_id, next_attempt_at);
+
+        metrics::QUEUE_DEPTH.with_label_values(&[&job.priority.to_string()]).inc();
+
+        Some(job.clone())
+    }
+
+    pub fn cancel_job(&self, job_id: Uuid) -> Result<Job, CancelError> {
+        // A job that finished before the last restart is only in the store, it's read before taking the lock
+        let stored = {
+            let index = self.jobs();
+            !index.jobs.contains_key(&job_id) && !index.evicted.contains_key(&job_id)
+        }.then(|| self.store.get_job(job_id).ok().flatten()).flatten();
+
+        let (job, canceled) = {
+            let mut index = self.jobs_mut();
+
+            // An evicted job has already finished
+            let Some(job) = index.jobs.get(&job_id).cloned() else {
+                let status = index.evicted_status(job_id).cloned()
+                    .or_else(|| stored.filter(|job| !job.is_recurring).map(|job| job.status));
+
+                return match status {
+                    Some(status @ (JobStatus::COMPLETED | JobStatus::FAILED | JobStatus::CANCELED)) => Err(CancelError::AlreadyFinished(status)),
+                    _ => Err(CancelError::NotFound)
+                };
+            };
+
+            match job.status {
+                JobStatus::PENDING | JobStatus::WAITING | JobStatus::RETRYING | JobStatus::SCHEDULED | JobStatus::RUNNING => {},
+                status => return Err(CancelError::AlreadyFinished(status))
             }
+
+            self.update_job_status(&mut index, job_id, JobStatus::CANCELED);
+
+            (job, index.jobs.get(&job_id).cloned().ok_or(CancelError::NotFound))
+        };
+
+        // Already CANCELED in the index, so a copy a worker is being handed right now gets dropped instead
+        match job.status {
+            JobStatus::PENDING | JobStatus::WAITING | JobStatus::RETRYING => {
+                if self.ready().remove(job_id).is_some() {
+                    metrics::QUEUE_DEPTH.with_label_values(&[&job.priority.to_string()]).dec();
+                }
+
+                if job.status == JobStatus::WAITING {
+                    metrics::JOBS_WAITING_TOTAL.dec();
+                }
+            },
+            JobStatus::SCHEDULED => {
+                self.ready().delayed.retain(|(_, id)| *id != job_id);
+            },
+            JobStatus::RUNNING => {
+                let mut registry = self.workers_mut();
+
+                let owner = registry.workers.values()
+                    .find(|w| w.current_job_ids.contains(&job_id))
+                    .map(|w| w.worker_id);
+
+                // The worker is told to kill the process on its next heartbeat, the results it posts afterwards are kept but won't change the status
+                if let Some(worker_id) = owner {
+                    registry.cancel_requests.entry(worker_id).or_default().push(job_id);
+                    log::info!("Requested worker {} to kill job id: {}", worker_id, job_id);
+                }
+            },
+            _ => {}
         }
+
+        canceled
     }
 
-    pub fn update_job_status(&mut self, job_id: Uuid, status: JobStatus) {
-        if let Some(job) = self.jobs.get_mut(&job_id) {
-            match db::update_job_status(&self.connection, job_id, status.clone()){
+    fn update_job_status(&self, index: &mut JobIndex, job_id: Uuid, status: JobStatus) {
+        if let Some(job) = index.jobs.get_mut(&job_id) {
+            match self.store.update_job_status(job_id, status.clone()){
                 Ok(_) => {},
                 Err(err) => {log::error!("DB Error: Failed update status for job id: {}\n Error output: {:?}", job_id, err)}   
             }
@@ -371,18 +1441,160 @@ impl JobQueue {
                 metrics::JOBS_COMPLETED_TOTAL.inc();
             } else if status == JobStatus::FAILED {
                 metrics::JOBS_FAILED_TOTAL.inc();
+            } else if status == JobStatus::CANCELED {
+                metrics::JOBS_CANCELED_TOTAL.inc();
+            }
+
+            if matches!(status, JobStatus::COMPLETED | JobStatus::FAILED | JobStatus::CANCELED) {
+                index.finished.insert((Utc::now(), job_id));
             }
 
             job.status = status;
         }
     }
 
-    pub fn store_results(&mut self, job_id: Uuid, job_results: JobResult) {
-        match db::insert_results(&self.connection, job_id, job_results.clone()) {
+    fn store_results(&self, index: &mut JobIndex, job_id: Uuid, job_results: JobResult) {
+        match self.store.insert_results(job_id, job_results.clone()) {
             Ok(_) => {},
             Err(err) => {log::error!("DB Error: Failed update insert results into the database. Job id: {}\n Error output: {:?}", job_id, err)}   
         }
 
-        self.results.insert(job_id, job_results);
+        index.results.insert(job_id, job_results);
+    }
+
+    // Drops jobs that finished over JOB_RETENTION_SECS ago, a job canceled mid-run is kept until its lease
+    // runs out so the killed process can still post its output. Returns how many were evicted
+    pub fn evict_finished_jobs(&self) -> usize {
+        let now = Utc::now();
+        let cutoff = now - *JOB_RETENTION;
+        let mut guard = self.jobs_mut();
+        let index = &mut *guard;
+        let mut evicted = 0;
+
+        index.forget_evicted_before(cutoff);
+
+        while let Some((finished_at, job_id)) = index.finished.first().copied() && finished_at <= cutoff {
+            index.finished.pop_first();
+
+            // Requeued from the dead letter queue since, it gets a new entry when it finishes again
+            let Some(job) = index.jobs.get(&job_id).filter(|j| matches!(j.status, JobStatus::COMPLETED | JobStatus::FAILED | JobStatus::CANCELED)) else {
+                continue;
+            };
+
+            if job.status == JobStatus::CANCELED && let Some(expires_at) = job.lease_expires_at && expires_at > now {
+                index.finished.insert((expires_at, job_id));
+                continue;
+            }
+
+            let status = job.status.clone();
+            index.remember_evicted(job_id, status, now);
+            index.jobs.remove(&job_id);
+            index.results.remove(&job_id);
+            evicted += 1;
+        }
+
+        evicted
+    }
+
+    pub fn record_attempt(&self, job_id: Uuid, worker_id: Uuid, started_at: DateTime<Utc>, finished_at: DateTime<Utc>, result: JobResult) {
+        match self.store.insert_attempt(job_id, worker_id, started_at, finished_at, result) {
+            Ok(_) => {},
+            Err(err) => {log::error!("DB Error: Failed to record attempt for job id: {}\n Error output: {:?}", job_id, err)}   
+        }
+    }
+
+    // Dead Letter Functions
+
+    pub fn dead_letter_job(&self, job_id: Uuid, reason: String) {
+        match self.store.insert_dead_letter(job_id, reason, Utc::now()) {
+            Ok(_) => {},
+            Err(err) => {log::error!("DB Error: Failed to move job id: {} to the dead letter queue\n Error output: {:?}", job_id, err)}   
+        }
+
+        metrics::JOBS_DEAD_LETTERED_TOTAL.inc();
+    }
+
+    pub fn get_dead_letters(&self) -> Result<Vec<DeadLetter>, StoreError> {
+        self.store.get_dead_letters()
     }
-}
\ No newline at end of file
+
+    // Gives the job a fresh set of attempts and puts it back in the queue under the same id
+    pub fn requeue_dead_letter(&self, job_id: Uuid) -> Option<Job> {
+        match self.store.delete_dead_letter(job_id) {
+            Ok(true) => {},
+            Ok(false) => return None,
+            Err(err) => {
+                log::error!("DB Error: Failed to remove job id: {} from the dead letter queue\n Error output: {:?}", job_id, err);
+                return None;
+            }
+        }
+
+        // An evicted job is read back from the store before taking the lock
+        let stored = (!self.jobs().jobs.contains_key(&job_id)).then(|| self.store.get_job(job_id).ok().flatten()).flatten();
+
+        let mut index = self.jobs_mut();
+
+        let mut job = match index.jobs.get(&job_id).cloned().or(stored) {
+            Some(job) => job,
+            // Evicted after the check above
+            None => {
+                drop(index);
+                let job = self.store.get_job(job_id).ok().flatten()?;
+                index = self.jobs_mut();
+                job
+            }
+        };
+
+        job.status = JobStatus::PENDING;
+        job.retry_count = 0;
+        job.next_attempt_at = None;
+        job.lease_expires_at = None;
+
+        match self.store.update_job_status(job_id, JobStatus::PENDING) {
+            Ok(_) => {},
+            Err(err) => {log::error!("DB Error: Failed update status for job id: {}\n Error output: {:?}", job_id, err)}   
+        }
+
+        match self.store.update_retry_count(job_id, 0) {
+            Ok(_) => {},
+            Err(err) => {log::error!("DB Error: Failed update retry count for job id: {}\n Error output: {:?}", job_id, err)}   
+        }
+
+        index.evicted.remove(&job_id);
+        index.jobs.insert(job_id, job.clone());
+        drop(index);
+
+        metrics::QUEUE_DEPTH.with_label_values(&[&job.priority.to_string()]).inc();
+
+        self.ready().push(job.clone());
+
+        Some(job)
+    }
+
+    // The job itself stays FAILED, it just stops showing up in the dead letter queue
+    pub fn purge_dead_letter(&self, job_id: Uuid) -> bool {
+        match self.store.delete_dead_letter(job_id) {
+            Ok(deleted) => deleted,
+            Err(err) => {
+                log::error!("DB Error: Failed to remove job id: {} from the dead letter queue\n Error output: {:?}", job_id, err);
+                false
+            }
+        }
+    }
+}
+
+// Delay before the given retry, growing by the multiplier each time up to the max and spread out by the jitter
+fn backoff_delay(policy: &RetryPolicy, retry_count: u32) -> Duration {
+    let exponent = retry_count.saturating_sub(1) as i32;
+    let max_delay = policy.max_delay_secs.min(MAX_RETRY_DELAY_SECS) as f64;
+    let delay = (policy.initial_delay_secs as f64 * policy.multiplier.powi(exponent)).min(max_delay);
+
+    let jitter = if policy.jitter > 0.0 {
+        delay * policy.jitter * rand::random_range(-1.0..=1.0)
+    } else {
+        0.0
+    };
+
+    Duration::try_milliseconds(((delay + jitter).max(0.0) * 1000.0) as i64)
+        .unwrap_or_else(|| Duration::seconds(MAX_RETRY_DELAY_SECS as i64))
+}
@@ -0,0 +1,254 @@
+use chrono::{
+    DateTime,
+    Duration,
+    LocalResult,
+    NaiveDateTime,
+    TimeZone,
+    Timelike,
+    Utc
+};
+use chrono_tz::Tz;
+use common::{calendar::{BlackoutWindow, Calendar}, duration::parse_duration, job::Job};
+use cron::Schedule;
+use std::str::FromStr;
+
+// Enough to walk past a whole DST gap minute by minute
+const MAX_GAP_MINUTES: i64 = 180;
+
+// Stops a cron that never matches (e.g. Feb 30th) from looping forever
+const MAX_CANDIDATES: usize = 1000;
+
+// Longest interval and jitter a schedule can be given, a year and a day
+pub const MAX_INTERVAL_SECS: u64 = 365 * 24 * 60 * 60;
+pub const MAX_JITTER_SECS: u64 = 24 * 60 * 60;
+
+// Longest a recurring blackout can last, a year
+pub const MAX_BLACKOUT_SECS: u64 = 365 * 24 * 60 * 60;
+
+// Accepts 5 and 6 field expressions, 5 field ones get a 0 seconds field added
+pub fn normalize_cron(expr: &str) -> Option<String> {
+    if expr.split_whitespace().count() == 5 {
+        let with_seconds = format!("0 {}", expr);
+        if Schedule::from_str(&with_seconds).is_ok() {
+            return Some(with_seconds);
+        }
+    }
+
+    Schedule::from_str(expr).ok().map(|_| expr.to_string())
+}
+
+// Interval length in whole seconds, at least 1 and at most MAX_INTERVAL_SECS
+pub fn parse_interval(every: &str) -> Option<u64> {
+    parse_duration(every).ok()
+        .map(|d| d.num_seconds())
+        .filter(|secs| *secs >= 1)
+        .map(|secs| secs as u64)
+        .filter(|secs| *secs <= MAX_INTERVAL_SECS)
+}
+
+// None is UTC
+pub fn parse_timezone(timezone: Option<&str>) -> Result<Tz, String> {
+    match timezone {
+        Some(name) => Tz::from_str(name).map_err(|_| format!("Unknown timezone: {}", name)),
+        None => Ok(Tz::UTC)
+    }
+}
+
+// Next time the schedule of `job` fires strictly after `after`, None once it's past its bounds or out of runs
+pub fn next_occurrence(job: &Job, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    if runs_left(job) == Some(0) {
+        return None;
+    }
+
+    // Run times are whole seconds so backing off a nanosecond makes not_before inclusive
+    let after = match job.not_before {
+        Some(not_before) if not_before > after => not_before - Duration::nanoseconds(1),
+        _ => after
+    };
+
+    next_time(job, after).filter(|next| job.not_after.is_none_or(|not_after| *next <= not_after))
+}
+
+// Every time the schedule fires in [from, to], ignoring its bounds and run limit. At most `limit` are returned
+pub fn occurrences_between(job: &Job, from: DateTime<Utc>, to: DateTime<Utc>, limit: usize) -> Vec<DateTime<Utc>> {
+    let mut runs = vec![];
+    let mut after = from - Duration::nanoseconds(1);
+
+    while runs.len() < limit && let Some(next) = next_time(job, after) && next <= to {
+        runs.push(next);
+        after = next;
+    }
+
+    runs
+}
+
+fn next_time(job: &Job, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    match job.interval_secs {
+        Some(secs) => next_interval(job.anchor?, secs, after),
+        None => {
+            let schedule = Schedule::from_str(job.schedule.as_deref()?).ok()?;
+            let tz = parse_timezone(job.timezone.as_deref()).ok()?;
+
+            next_in_timezone(&schedule, tz, after)
+        }
+    }
+}
+
+// First anchor + k * interval strictly after `after`, the anchor itself counts when it's still ahead
+pub fn next_interval(anchor: DateTime<Utc>, interval_secs: u64, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    if anchor > after {
+        return Some(anchor);
+    }
+
+    let interval = i64::try_from(interval_secs.max(1)).ok()?;
+    let elapsed = (after - anchor).num_seconds();
+    let offset = (elapsed / interval).checked_add(1)?.checked_mul(interval)?;
+
+    anchor.checked_add_signed(Duration::try_seconds(offset)?)
+}
+
+// None when the schedule has no run limit
+pub fn runs_left(job: &Job) -> Option<u32> {
+    job.max_runs.map(|max| max.saturating_sub(job.run_count))
+}
+
+// The cron is matched against wall clock time in `tz`, then each match is turned into an instant:
+// - a time repeated when the clocks go back runs once, on its first occurrence
+// - a time skipped when the clocks go forward runs at the first instant after the gap
+pub fn next_in_timezone(schedule: &Schedule, tz: Tz, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    // Wall clock times are walked as if they were UTC so cron never sees the offsets
+    let mut wall = Utc.from_utc_datetime(&after.with_timezone(&tz).naive_local());
+
+    for _ in 0..MAX_CANDIDATES {
+        wall = schedule.after(&wall).next()?;
+
+        if let Some(instant) = resolve_local(tz, wall.naive_utc())
+            && instant > after {
+            return Some(instant);
+        }
+    }
+
+    None
+}
+
+// End of the blackout `at` falls in, following windows that run into each other. None if `at` is clear
+pub fn blackout_end(calendars: &[&Calendar], at: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let mut end = None;
+    let mut t = at;
+
+    for _ in 0..MAX_CANDIDATES {
+        let window_end = calendars.iter()
+            .flat_map(|c| c.windows.iter())
+            .filter_map(|w| window_end(w, t))
+            .max();
+
+        match window_end {
+            Some(e) => {
+                end = Some(e);
+                t = e;
+            },
+            None => break
+        }
+    }
+
+    end
+}
+
+fn window_end(window: &BlackoutWindow, at: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    match window {
+        BlackoutWindow::RANGE { start, end } => (*start <= at && at < *end).then_some(*end),
+        BlackoutWindow::RECURRING { cron, duration_secs, timezone } => {
+            let schedule = Schedule::from_str(cron).ok()?;
+            let tz = parse_timezone(timezone.as_deref()).ok()?;
+            // Windows stored before the cap was checked are held to it too
+            let duration = Duration::try_seconds((*duration_secs).min(MAX_BLACKOUT_SECS) as i64)?;
+
+            // The earliest window still open at `at` starts after at - duration
+            let start = next_in_timezone(&schedule, tz, at.checked_sub_signed(duration)?)?;
+
+            start.checked_add_signed(duration).filter(|_| start <= at)
+        }
+    }
+}
+
+// Checks a window and stores its cron in the 6 field form
+pub fn normalize_window(window: BlackoutWindow) -> Result<BlackoutWindow, String> {
+    match window {
+        BlackoutWindow::RANGE { start, end } if start >= end => Err(String::from("A blackout range must start before it ends.")),
+        BlackoutWindow::RANGE { .. } => Ok(window),
+        BlackoutWindow::RECURRING { cron, duration_secs, timezone } => {
+            let cron = normalize_cron(&cron).ok_or_else(|| format!("Invalid cron schedule: {}", cron))?;
+            parse_timezone(timezone.as_deref())?;
+
+            if duration_secs == 0 {
+                return Err(String::from("A recurring blackout must last at least 1 second."));
+            }
+
+            if duration_secs > MAX_BLACKOUT_SECS {
+                return Err(String::from("A recurring blackout can't last longer than a year."));
+            }
+
+            Ok(BlackoutWindow::RECURRING { cron, duration_secs, timezone })
+        }
+    }
+}
+
+fn resolve_local(tz: Tz, local: NaiveDateTime) -> Option<DateTime<Utc>> {
+    match tz.from_local_datetime(&local) {
+        LocalResult::Single(t) => Some(t.with_timezone(&Utc)),
+        LocalResult::Ambiguous(earliest, _) => Some(earliest.with_timezone(&Utc)),
+        // Gaps start and end on a whole minute, so counting whole minutes from the start of this one
+        // lands on the transition itself
+        LocalResult::None => {
+            let minute = local.with_second(0)?.with_nanosecond(0)?;
+
+            (1..=MAX_GAP_MINUTES)
+                .find_map(|m| tz.from_local_datetime(&(minute + Duration::minutes(m))).earliest())
+                .map(|t| t.with_timezone(&Utc))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::America::New_York;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn next(cron: &str, after: &str) -> Option<DateTime<Utc>> {
+        let schedule = Schedule::from_str(&normalize_cron(cron).unwrap()).unwrap();
+        next_in_timezone(&schedule, New_York, utc(after))
+    }
+
+    // 2026-03-08 02:00 EST jumps to 03:00 EDT
+    #[test]
+    fn spring_forward_gap_runs_at_the_transition() {
+        assert_eq!(next("30 2 * * *", "2026-03-08T00:00:00-05:00"), Some(utc("2026-03-08T03:00:00-04:00")));
+        assert_eq!(next("15 30 2 * * *", "2026-03-08T00:00:00-05:00"), Some(utc("2026-03-08T03:00:00-04:00")));
+
+        // The day after is back to 02:30
+        assert_eq!(next("30 2 * * *", "2026-03-08T03:00:00-04:00"), Some(utc("2026-03-09T02:30:00-04:00")));
+    }
+
+    #[test]
+    fn gap_resolves_to_the_first_whole_minute_after_it() {
+        let local = |s: &str| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap();
+
+        assert_eq!(resolve_local(New_York, local("2026-03-08 02:30:15")), Some(utc("2026-03-08T03:00:00-04:00")));
+        assert_eq!(resolve_local(New_York, local("2026-03-08 02:59:59")), Some(utc("2026-03-08T03:00:00-04:00")));
+        assert_eq!(resolve_local(New_York, local("2026-03-08 03:00:15")), Some(utc("2026-03-08T03:00:15-04:00")));
+    }
+
+    // 2026-11-01 02:00 EDT goes back to 01:00 EST, so 01:30 happens twice
+    #[test]
+    fn fall_back_runs_a_repeated_time_once() {
+        assert_eq!(next("30 1 * * *", "2026-11-01T00:00:00-04:00"), Some(utc("2026-11-01T01:30:00-04:00")));
+        assert_eq!(next("30 1 * * *", "2026-11-01T01:30:00-04:00"), Some(utc("2026-11-02T01:30:00-05:00")));
+
+        // Times after the repeated hour keep their usual offset
+        assert_eq!(next("0 2 * * *", "2026-11-01T00:00:00-04:00"), Some(utc("2026-11-01T02:00:00-05:00")));
+    }
+}
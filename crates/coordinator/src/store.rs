@@ -0,0 +1,167 @@
+use common::{
+    calendar::Calendar,
+    job::{
+        JobAttempt,
+        JobResult,
+        JobStatus,
+        Job,
+    },
+    message::{DeadLetter, ScheduleRun}
+};
+use std::{fmt, str::FromStr};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+pub mod memory;
+pub mod postgres;
+pub mod sqlite;
+
+#[cfg(test)]
+mod tests;
+
+// SQLite file used when DATABASE_URL isn't set
+pub const DEFAULT_SQLITE_PATH: &str = "scheduler.db";
+
+// Everything `JobQueue` keeps outside of memory. The queue calls it from any handler without a lock
+// of its own, so implementations have to be Sync. A write may be queued and committed later,
+// as long as the store's own reads see it
+pub trait JobStore: Send + Sync {
+    // Jobs
+    fn insert_job(&self, job: Job) -> Result<(), StoreError>;
+    fn update_job_status(&self, job_id: Uuid, status: JobStatus) -> Result<(), StoreError>;
+    fn update_retry_count(&self, job_id: Uuid, count: u32) -> Result<(), StoreError>;
+    fn update_next_attempt(&self, job_id: Uuid, next_attempt_at: DateTime<Utc>) -> Result<(), StoreError>;
+    fn update_job_lease(&self, job_id: Uuid, token: u64, expires_at: DateTime<Utc>) -> Result<(), StoreError>;
+    // Lease tokens have to keep increasing across restarts so old tokens stay invalid
+    fn max_lease_token(&self) -> Result<u64, StoreError>;
+
+    fn get_job(&self, job_id: Uuid) -> Result<Option<Job>, StoreError>;
+    // Oldest first, every job when `status` is None
+    fn get_job_list(&self, status: Option<JobStatus>) -> Result<Vec<Job>, StoreError>;
+    // Every job that isn't finished yet, oldest first so the queues keep their order
+    fn load_unfinished_jobs(&self) -> Result<Vec<Job>, StoreError>;
+
+    // Results and attempts
+    fn insert_results(&self, job_id: Uuid, results: JobResult) -> Result<(), StoreError>;
+    // Latest result, a requeued job can have one per finished run
+    fn get_result(&self, job_id: Uuid) -> Result<Option<JobResult>, StoreError>;
+    // The attempt number keeps counting up after a requeue resets the retry count
+    fn insert_attempt(&self, job_id: Uuid, worker_id: Uuid, started_at: DateTime<Utc>, finished_at: DateTime<Utc>, result: JobResult) -> Result<(), StoreError>;
+    fn get_attempts(&self, job_id: Uuid) -> Result<Vec<JobAttempt>, StoreError>;
+
+    // Schedules
+    fn update_schedule_run(&self, id: Uuid, next_run: DateTime<Utc>) -> Result<(), StoreError>;
+    // Command, args, priority, cron or interval and next run
+    fn update_schedule(&self, job: &Job) -> Result<(), StoreError>;
+    fn update_run_count(&self, id: Uuid, run_count: u32) -> Result<(), StoreError>;
+    // Newest first
+    fn get_schedule_runs(&self, schedule_id: Uuid, limit: usize) -> Result<Vec<ScheduleRun>, StoreError>;
+
+    // Calendars
+    fn upsert_calendar(&self, calendar: &Calendar) -> Result<(), StoreError>;
+    fn get_calendars(&self) -> Result<Vec<Calendar>, StoreError>;
+    fn delete_calendar(&self, name: &str) -> Result<bool, StoreError>;
+
+    // Dead letters
+    fn insert_dead_letter(&self, job_id: Uuid, reason: String, failed_at: DateTime<Utc>) -> Result<(), StoreError>;
+    // Oldest failure first, letters whose job is gone are skipped
+    fn get_dead_letters(&self) -> Result<Vec<DeadLetter>, StoreError>;
+    fn delete_dead_letter(&self, job_id: Uuid) -> Result<bool, StoreError>;
+}
+
+#[derive(Debug)]
+pub enum StoreError {
+    Sqlite(rusqlite::Error),
+    Postgres(::postgres::Error),
+    // A row that couldn't be turned back into its type
+    Malformed(String),
+    // The connection thread behind a store has gone away
+    Disconnected
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StoreError::Sqlite(err) => write!(f, "{}", err),
+            StoreError::Postgres(err) => write!(f, "{}", err),
+            StoreError::Malformed(msg) => write!(f, "Malformed row: {}", msg),
+            StoreError::Disconnected => write!(f, "The store connection is closed")
+        }
+    }
+}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(err: rusqlite::Error) -> Self {
+        StoreError::Sqlite(err)
+    }
+}
+
+impl From<::postgres::Error> for StoreError {
+    fn from(err: ::postgres::Error) -> Self {
+        StoreError::Postgres(err)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Sqlite,
+    Memory,
+    Postgres
+}
+
+impl FromStr for Backend {
+    type Err = &'static str;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.to_ascii_lowercase().as_str() {
+            "sqlite" => Ok(Backend::Sqlite),
+            "memory" => Ok(Backend::Memory),
+            "postgres" | "postgresql" => Ok(Backend::Postgres),
+
+            _ => Err("Invalid Store Backend")
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Backend::Sqlite => write!(f, "sqlite"),
+            Backend::Memory => write!(f, "memory"),
+            Backend::Postgres => write!(f, "postgres")
+        }
+    }
+}
+
+// STORE_BACKEND picks the backend (sqlite by default)
+pub fn backend() -> Result<Backend, String> {
+    match std::env::var("STORE_BACKEND") {
+        Ok(name) => Backend::from_str(&name).map_err(|_| format!("Unknown STORE_BACKEND: {} (expected sqlite, memory or postgres)", name)),
+        Err(_) => Ok(Backend::Sqlite)
+    }
+}
+
+// SQLite reads DATABASE_URL as a file path
+pub fn sqlite_path() -> String {
+    std::env::var("DATABASE_URL").unwrap_or_else(|_| DEFAULT_SQLITE_PATH.to_string())
+}
+
+pub fn open(backend: Backend) -> Result<Box<dyn JobStore>, String> {
+    match backend {
+        Backend::Sqlite => {
+            let path = sqlite_path();
+
+            sqlite::SqliteStore::open(&path)
+                .map(|store| Box::new(store) as Box<dyn JobStore>)
+                .map_err(|err| format!("Failed to open {}: {}", path, err))
+        },
+        Backend::Memory => Ok(Box::new(memory::MemoryStore::new())),
+        Backend::Postgres => {
+            let url = std::env::var("DATABASE_URL").map_err(|_| String::from("DATABASE_URL has to be set to use the postgres backend"))?;
+
+            postgres::PostgresStore::connect(&url)
+                .map(|store| Box::new(store) as Box<dyn JobStore>)
+                .map_err(|err| format!("Failed to connect to Postgres: {}", err))
+        }
+    }
+}
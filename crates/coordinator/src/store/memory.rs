@@ -0,0 +1,234 @@
+use common::{
+    calendar::Calendar,
+    job::{
+        JobAttempt,
+        JobResult,
+        JobStatus,
+        Job,
+    },
+    message::{DeadLetter, ScheduleRun}
+};
+use std::{collections::{BTreeMap, HashMap}, sync::{Mutex, MutexGuard}};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use super::{JobStore, StoreError};
+
+// Keeps everything in the process, nothing survives a restart. Meant for tests and throwaway coordinators
+pub struct MemoryStore {
+    state: Mutex<MemoryState>
+}
+
+#[derive(Default)]
+struct MemoryState {
+    jobs: HashMap<Uuid, Job>,
+    // Every result a job has posted, oldest first
+    results: HashMap<Uuid, Vec<JobResult>>,
+    attempts: HashMap<Uuid, Vec<JobAttempt>>,
+    calendars: BTreeMap<String, Calendar>,
+    dead_letters: HashMap<Uuid, (String, DateTime<Utc>)>
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        MemoryStore { state: Mutex::new(MemoryState::default()) }
+    }
+
+    // A panic while holding the lock can't leave the maps half written, so a poisoned lock is still usable
+    fn state(&self) -> MutexGuard<'_, MemoryState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn update_job(&self, job_id: Uuid, update: impl FnOnce(&mut Job)) -> Result<(), StoreError> {
+        if let Some(job) = self.state().jobs.get_mut(&job_id) {
+            update(job);
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Oldest first, same as the SQL backends
+fn sorted_by_timestamp<'a>(jobs: impl Iterator<Item = &'a Job>) -> Vec<Job> {
+    let mut jobs: Vec<Job> = jobs.cloned().collect();
+    jobs.sort_by_key(|job| job.timestamp);
+
+    jobs
+}
+
+impl JobStore for MemoryStore {
+    fn insert_job(&self, job: Job) -> Result<(), StoreError> {
+        self.state().jobs.insert(job.id, job);
+
+        Ok(())
+    }
+
+    fn update_job_status(&self, job_id: Uuid, status: JobStatus) -> Result<(), StoreError> {
+        self.update_job(job_id, |job| job.status = status)
+    }
+
+    fn update_retry_count(&self, job_id: Uuid, count: u32) -> Result<(), StoreError> {
+        self.update_job(job_id, |job| job.retry_count = count)
+    }
+
+    fn update_next_attempt(&self, job_id: Uuid, next_attempt_at: DateTime<Utc>) -> Result<(), StoreError> {
+        self.update_job(job_id, |job| job.next_attempt_at = Some(next_attempt_at))
+    }
+
+    fn update_job_lease(&self, job_id: Uuid, token: u64, expires_at: DateTime<Utc>) -> Result<(), StoreError> {
+        self.update_job(job_id, |job| {
+            job.lease_token = Some(token);
+            job.lease_expires_at = Some(expires_at);
+        })
+    }
+
+    fn max_lease_token(&self) -> Result<u64, StoreError> {
+        Ok(self.state().jobs.values().filter_map(|job| job.lease_token).max().unwrap_or(0))
+    }
+
+    fn get_job(&self, job_id: Uuid) -> Result<Option<Job>, StoreError> {
+        Ok(self.state().jobs.get(&job_id).cloned())
+    }
+
+    fn get_job_list(&self, status: Option<JobStatus>) -> Result<Vec<Job>, StoreError> {
+        let state = self.state();
+
+        Ok(sorted_by_timestamp(state.jobs.values().filter(|job| status.as_ref().is_none_or(|s| job.status == *s))))
+    }
+
+    fn load_unfinished_jobs(&self) -> Result<Vec<Job>, StoreError> {
+        let state = self.state();
+
+        Ok(sorted_by_timestamp(state.jobs.values().filter(|job| matches!(job.status,
+            JobStatus::PENDING | JobStatus::RUNNING | JobStatus::RETRYING | JobStatus::WAITING | JobStatus::SCHEDULED | JobStatus::PAUSED
+        ))))
+    }
+
+    fn insert_results(&self, job_id: Uuid, results: JobResult) -> Result<(), StoreError> {
+        self.state().results.entry(job_id).or_default().push(results);
+
+        Ok(())
+    }
+
+    fn get_result(&self, job_id: Uuid) -> Result<Option<JobResult>, StoreError> {
+        Ok(self.state().results.get(&job_id).and_then(|results| results.last().cloned()))
+    }
+
+    fn insert_attempt(&self, job_id: Uuid, worker_id: Uuid, started_at: DateTime<Utc>, finished_at: DateTime<Utc>, result: JobResult) -> Result<(), StoreError> {
+        let mut state = self.state();
+        let attempts = state.attempts.entry(job_id).or_default();
+
+        attempts.push(JobAttempt {
+            attempt: attempts.len() as u32 + 1,
+            worker_id,
+            started_at,
+            finished_at,
+            result
+        });
+
+        Ok(())
+    }
+
+    fn get_attempts(&self, job_id: Uuid) -> Result<Vec<JobAttempt>, StoreError> {
+        Ok(self.state().attempts.get(&job_id).cloned().unwrap_or_default())
+    }
+
+    fn update_schedule_run(&self, id: Uuid, next_run: DateTime<Utc>) -> Result<(), StoreError> {
+        self.update_job(id, |job| job.next_run = Some(next_run))
+    }
+
+    fn update_schedule(&self, job: &Job) -> Result<(), StoreError> {
+        self.update_job(job.id, |stored| {
+            stored.command = job.command.clone();
+            stored.args = job.args.clone();
+            stored.priority = job.priority.clone();
+            stored.schedule = job.schedule.clone();
+            stored.interval_secs = job.interval_secs;
+            stored.anchor = job.anchor;
+            stored.next_run = job.next_run;
+        })
+    }
+
+    fn update_run_count(&self, id: Uuid, run_count: u32) -> Result<(), StoreError> {
+        self.update_job(id, |job| job.run_count = run_count)
+    }
+
+    fn get_schedule_runs(&self, schedule_id: Uuid, limit: usize) -> Result<Vec<ScheduleRun>, StoreError> {
+        let state = self.state();
+
+        let mut children: Vec<&Job> = state.jobs.values()
+            .filter(|job| job.parent_schedule_id == Some(schedule_id))
+            .collect();
+        children.sort_by_key(|job| std::cmp::Reverse(job.timestamp));
+
+        Ok(children.into_iter().take(limit).map(|job| {
+            let attempts = state.attempts.get(&job.id).map(Vec::as_slice).unwrap_or_default();
+            let started_at = attempts.iter().map(|a| a.started_at).min();
+            let finished_at = attempts.iter().map(|a| a.finished_at).max();
+
+            ScheduleRun {
+                job_id: job.id,
+                status: job.status.clone(),
+                created_at: job.timestamp,
+                scheduled_for: job.scheduled_for,
+                attempts: attempts.len() as u32,
+                exitcode: state.results.get(&job.id).and_then(|results| results.last()).map(|r| r.exitcode),
+                started_at,
+                finished_at,
+                duration_ms: started_at.zip(finished_at).map(|(start, finish)| (finish - start).num_milliseconds())
+            }
+        }).collect())
+    }
+
+    fn upsert_calendar(&self, calendar: &Calendar) -> Result<(), StoreError> {
+        self.state().calendars.insert(calendar.name.clone(), calendar.clone());
+
+        Ok(())
+    }
+
+    fn get_calendars(&self) -> Result<Vec<Calendar>, StoreError> {
+        Ok(self.state().calendars.values().cloned().collect())
+    }
+
+    fn delete_calendar(&self, name: &str) -> Result<bool, StoreError> {
+        Ok(self.state().calendars.remove(name).is_some())
+    }
+
+    fn insert_dead_letter(&self, job_id: Uuid, reason: String, failed_at: DateTime<Utc>) -> Result<(), StoreError> {
+        self.state().dead_letters.insert(job_id, (reason, failed_at));
+
+        Ok(())
+    }
+
+    fn get_dead_letters(&self) -> Result<Vec<DeadLetter>, StoreError> {
+        let state = self.state();
+
+        let mut letters: Vec<DeadLetter> = state.dead_letters.iter()
+            .filter_map(|(job_id, (reason, failed_at))| match state.jobs.get(job_id) {
+                Some(job) => Some(DeadLetter {
+                    job: job.clone(),
+                    reason: reason.clone(),
+                    failed_at: *failed_at,
+                    attempts: state.attempts.get(job_id).cloned().unwrap_or_default()
+                }),
+                None => {
+                    log::warn!("Skipping dead letter for missing job id: {}", job_id);
+                    None
+                }
+            })
+            .collect();
+        letters.sort_by_key(|letter| letter.failed_at);
+
+        Ok(letters)
+    }
+
+    fn delete_dead_letter(&self, job_id: Uuid) -> Result<bool, StoreError> {
+        Ok(self.state().dead_letters.remove(&job_id).is_some())
+    }
+}
@@ -0,0 +1,330 @@
+use common::{
+    calendar::Calendar,
+    job::{
+        JobAttempt,
+        JobResult,
+        JobStatus,
+        Job,
+    },
+    message::{DeadLetter, ScheduleRun}
+};
+use rusqlite::{Connection, Error};
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, PoisonError, mpsc},
+    thread,
+    time::Duration
+};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::db;
+use super::{JobStore, StoreError};
+
+// Most writes committed in one transaction
+const MAX_BATCH: usize = 512;
+
+// Checkpoints can hold the file for a moment even in WAL mode
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+type Write = Box<dyn FnOnce(&Connection) -> Result<(), Error> + Send>;
+
+// The part of a job a write goes to, a read of one part only waits for the writes to that part
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Part {
+    Row,
+    Result,
+    Attempts
+}
+
+// How many writes to each part of a job are queued but not committed yet
+type Pending = Arc<Mutex<HashMap<(Uuid, Part), usize>>>;
+
+enum Command {
+    // What the write does, for the error log, the part of a job it goes to if any, and the write itself
+    Write(&'static str, Option<(Uuid, Part)>, Write),
+    // Answered once every write queued before it is committed
+    Flush(mpsc::SyncSender<()>)
+}
+
+// The original backend, the queries themselves live in `db`.
+// Writes are handed to a writer thread and return straight away, so the queue lock is never held
+// across an fsync. The thread commits whatever has queued up in one transaction, a write that fails
+// is logged and the rest of its batch still goes in. Reads flush first so they always see earlier writes,
+// a read of one job's row, result or attempts only when writes to it are still queued
+pub struct SqliteStore {
+    // Reads only, WAL lets them run alongside the writer's transactions
+    connection: Mutex<Connection>,
+    writer: mpsc::Sender<Command>,
+    pending: Pending
+}
+
+impl SqliteStore {
+    // Opens (or creates) the database file, switches it to WAL and brings its schema up to date
+    pub fn open(path: &str) -> Result<Self, StoreError> {
+        let mut writer = Connection::open(path)?;
+        writer.busy_timeout(BUSY_TIMEOUT)?;
+
+        let journal_mode: String = writer.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get(0))?;
+        if !journal_mode.eq_ignore_ascii_case("wal") {
+            log::warn!("SQLite is using the {} journal instead of WAL, reads will wait on writes", journal_mode);
+        }
+
+        db::init(&mut writer)?;
+
+        let connection = Connection::open(path)?;
+        connection.busy_timeout(BUSY_TIMEOUT)?;
+
+        let (sender, receiver) = mpsc::channel();
+        let pending = Pending::default();
+        let writer_pending = pending.clone();
+
+        thread::Builder::new()
+            .name(String::from("sqlite-writer"))
+            .spawn(move || run_writer(writer, receiver, writer_pending))
+            .map_err(|_| StoreError::Disconnected)?;
+
+        Ok(SqliteStore { connection: Mutex::new(connection), writer: sender, pending })
+    }
+
+    // Waits until everything written so far is committed
+    pub fn flush(&self) -> Result<(), StoreError> {
+        let (done, flushed) = mpsc::sync_channel(1);
+
+        self.writer.send(Command::Flush(done)).map_err(|_| StoreError::Disconnected)?;
+        flushed.recv().map_err(|_| StoreError::Disconnected)
+    }
+
+    // Like `flush`, but returns straight away when nothing for that part of the job is waiting to be committed
+    fn flush_job(&self, job_id: Uuid, part: Part) -> Result<(), StoreError> {
+        if pending_writes(&self.pending).contains_key(&(job_id, part)) {
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+
+    // A panic mid-read leaves nothing half done on a read-only connection
+    fn reader(&self) -> MutexGuard<'_, Connection> {
+        self.connection.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn write(&self, what: &'static str, write: impl FnOnce(&Connection) -> Result<(), Error> + Send + 'static) -> Result<(), StoreError> {
+        self.writer.send(Command::Write(what, None, Box::new(write))).map_err(|_| StoreError::Disconnected)
+    }
+
+    // For writes to a single job, counted until they're committed so `flush_job` knows to wait for them
+    fn write_job(&self, job_id: Uuid, part: Part, what: &'static str, write: impl FnOnce(&Connection) -> Result<(), Error> + Send + 'static) -> Result<(), StoreError> {
+        *pending_writes(&self.pending).entry((job_id, part)).or_default() += 1;
+
+        self.writer.send(Command::Write(what, Some((job_id, part)), Box::new(write))).map_err(|_| StoreError::Disconnected)
+    }
+
+    // For writes whose answer is needed (did the delete hit a row), waits for its batch and hands any error back
+    fn write_and_wait<T: Send + 'static>(&self, what: &'static str, write: impl FnOnce(&Connection) -> Result<T, Error> + Send + 'static) -> Result<T, StoreError> {
+        let (reply, answer) = mpsc::sync_channel(1);
+
+        self.write(what, move |conn| {
+            let _ = reply.send(write(conn));
+            Ok(())
+        })?;
+
+        Ok(answer.recv().map_err(|_| StoreError::Disconnected)??)
+    }
+}
+
+// Anything still queued goes in before the store is gone
+impl Drop for SqliteStore {
+    fn drop(&mut self) {
+        if self.flush().is_err() {
+            log::error!("DB Error: The SQLite writer stopped before every write was committed");
+        }
+    }
+}
+
+// The writer thread counts a job's writes off once their batch is committed (or lost)
+fn pending_writes(pending: &Pending) -> MutexGuard<'_, HashMap<(Uuid, Part), usize>> {
+    pending.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn run_writer(mut conn: Connection, commands: mpsc::Receiver<Command>, pending: Pending) {
+    while let Ok(first) = commands.recv() {
+        let mut batch = vec![first];
+        while batch.len() < MAX_BATCH && let Ok(next) = commands.try_recv() {
+            batch.push(next);
+        }
+
+        let mut flushes = vec![];
+        let mut written = vec![];
+
+        match conn.transaction() {
+            Ok(tx) => {
+                for command in batch {
+                    match command {
+                        Command::Write(what, part, write) => {
+                            if let Err(err) = write(&tx) {
+                                log::error!("DB Error: Failed to {}.\n Error: {}", what, err);
+                            }
+
+                            written.extend(part);
+                        },
+                        Command::Flush(done) => flushes.push(done)
+                    }
+                }
+
+                if let Err(err) = tx.commit() {
+                    log::error!("DB Error: Failed to commit a batch of writes.\n Error: {}", err);
+                }
+            },
+            Err(err) => {
+                log::error!("DB Error: Failed to start a batch of {} writes, they're lost.\n Error: {}", batch.len(), err);
+
+                for command in batch {
+                    match command {
+                        Command::Write(_, part, _) => written.extend(part),
+                        Command::Flush(done) => flushes.push(done)
+                    }
+                }
+            }
+        }
+
+        let mut pending = pending_writes(&pending);
+        for part in written {
+            if let Entry::Occupied(mut count) = pending.entry(part) {
+                *count.get_mut() -= 1;
+
+                if *count.get() == 0 {
+                    count.remove();
+                }
+            }
+        }
+        drop(pending);
+
+        for done in flushes {
+            let _ = done.send(());
+        }
+    }
+}
+
+// A fresh database file in the temp dir, for the benchmarks and tests
+pub fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("scheduler-{}-{}.db", name, Uuid::new_v4()))
+}
+
+// Removes the file along with its WAL and shared memory files
+pub fn remove_db(path: &Path) {
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = fs::remove_file(format!("{}{}", path.to_string_lossy(), suffix));
+    }
+}
+
+impl JobStore for SqliteStore {
+    fn insert_job(&self, job: Job) -> Result<(), StoreError> {
+        self.write_job(job.id, Part::Row, "insert job", move |conn| db::insert_job(conn, job))
+    }
+
+    fn update_job_status(&self, job_id: Uuid, status: JobStatus) -> Result<(), StoreError> {
+        self.write_job(job_id, Part::Row, "update job status", move |conn| db::update_job_status(conn, job_id, status))
+    }
+
+    fn update_retry_count(&self, job_id: Uuid, count: u32) -> Result<(), StoreError> {
+        self.write_job(job_id, Part::Row, "update retry count", move |conn| db::update_retry_count(conn, job_id, count))
+    }
+
+    fn update_next_attempt(&self, job_id: Uuid, next_attempt_at: DateTime<Utc>) -> Result<(), StoreError> {
+        self.write_job(job_id, Part::Row, "update next attempt", move |conn| db::update_next_attempt(conn, job_id, next_attempt_at))
+    }
+
+    fn update_job_lease(&self, job_id: Uuid, token: u64, expires_at: DateTime<Utc>) -> Result<(), StoreError> {
+        self.write_job(job_id, Part::Row, "update job lease", move |conn| db::update_job_lease(conn, job_id, token, expires_at))
+    }
+
+    fn max_lease_token(&self) -> Result<u64, StoreError> {
+        self.flush()?;
+        Ok(db::max_lease_token(&self.reader())?)
+    }
+
+    fn get_job(&self, job_id: Uuid) -> Result<Option<Job>, StoreError> {
+        self.flush_job(job_id, Part::Row)?;
+        Ok(db::get_job(&self.reader(), job_id)?)
+    }
+
+    fn get_job_list(&self, status: Option<JobStatus>) -> Result<Vec<Job>, StoreError> {
+        self.flush()?;
+        Ok(db::get_job_list(&self.reader(), status)?)
+    }
+
+    fn load_unfinished_jobs(&self) -> Result<Vec<Job>, StoreError> {
+        self.flush()?;
+        Ok(db::load_unfinished_jobs(&self.reader())?)
+    }
+
+    fn insert_results(&self, job_id: Uuid, results: JobResult) -> Result<(), StoreError> {
+        self.write_job(job_id, Part::Result, "insert results", move |conn| db::insert_results(conn, job_id, results))
+    }
+
+    fn get_result(&self, job_id: Uuid) -> Result<Option<JobResult>, StoreError> {
+        self.flush_job(job_id, Part::Result)?;
+        Ok(db::get_result(&self.reader(), job_id)?)
+    }
+
+    fn insert_attempt(&self, job_id: Uuid, worker_id: Uuid, started_at: DateTime<Utc>, finished_at: DateTime<Utc>, result: JobResult) -> Result<(), StoreError> {
+        self.write_job(job_id, Part::Attempts, "insert attempt", move |conn| db::insert_attempt(conn, job_id, worker_id, started_at, finished_at, result))
+    }
+
+    fn get_attempts(&self, job_id: Uuid) -> Result<Vec<JobAttempt>, StoreError> {
+        self.flush_job(job_id, Part::Attempts)?;
+        Ok(db::get_attempts(&self.reader(), job_id)?)
+    }
+
+    fn update_schedule_run(&self, id: Uuid, next_run: DateTime<Utc>) -> Result<(), StoreError> {
+        self.write_job(id, Part::Row, "update schedule run", move |conn| db::update_schedule_run(conn, id, next_run))
+    }
+
+    fn update_schedule(&self, job: &Job) -> Result<(), StoreError> {
+        let job = job.clone();
+
+        self.write_job(job.id, Part::Row, "update schedule", move |conn| db::update_schedule(conn, &job))
+    }
+
+    fn update_run_count(&self, id: Uuid, run_count: u32) -> Result<(), StoreError> {
+        self.write_job(id, Part::Row, "update run count", move |conn| db::update_run_count(conn, id, run_count))
+    }
+
+    fn get_schedule_runs(&self, schedule_id: Uuid, limit: usize) -> Result<Vec<ScheduleRun>, StoreError> {
+        self.flush()?;
+        Ok(db::get_schedule_runs(&self.reader(), schedule_id, limit)?)
+    }
+
+    fn upsert_calendar(&self, calendar: &Calendar) -> Result<(), StoreError> {
+        let calendar = calendar.clone();
+
+        self.write("upsert calendar", move |conn| db::upsert_calendar(conn, &calendar))
+    }
+
+    fn get_calendars(&self) -> Result<Vec<Calendar>, StoreError> {
+        self.flush()?;
+        Ok(db::get_calendars(&self.reader())?)
+    }
+
+    fn delete_calendar(&self, name: &str) -> Result<bool, StoreError> {
+        let name = name.to_string();
+
+        self.write_and_wait("delete calendar", move |conn| db::delete_calendar(conn, &name))
+    }
+
+    fn insert_dead_letter(&self, job_id: Uuid, reason: String, failed_at: DateTime<Utc>) -> Result<(), StoreError> {
+        self.write("insert dead letter", move |conn| db::insert_dead_letter(conn, job_id, reason, failed_at))
+    }
+
+    fn get_dead_letters(&self) -> Result<Vec<DeadLetter>, StoreError> {
+        self.flush()?;
+        Ok(db::get_dead_letters(&self.reader())?)
+    }
+
+    fn delete_dead_letter(&self, job_id: Uuid) -> Result<bool, StoreError> {
+        self.write_and_wait("delete dead letter", move |conn| db::delete_dead_letter(conn, job_id))
+    }
+}
@@ -0,0 +1,331 @@
+use common::{
+    calendar::{BlackoutWindow, Calendar},
+    job::{JobResult, JobStatus, Priority, Job}
+};
+use chrono::{DateTime, Duration, Utc};
+use uuid::Uuid;
+
+use super::{JobStore, memory::MemoryStore, postgres::PostgresStore, sqlite::{SqliteStore, remove_db, temp_path}};
+
+// What every backend has to do the same way, the queue doesn't know which one it's talking to
+
+#[test]
+fn memory_store() {
+    check_store(&MemoryStore::new());
+}
+
+#[test]
+fn sqlite_store() {
+    let path = temp_path("store-test");
+
+    {
+        let store = SqliteStore::open(&path.to_string_lossy()).expect("Failed to open the SQLite store");
+        check_store(&store);
+    }
+
+    remove_db(&path);
+}
+
+// Needs a database to write to, run it with
+// POSTGRES_TEST_URL="host=localhost user=postgres dbname=scheduler_test" cargo test -- --ignored
+#[test]
+#[ignore = "needs a Postgres database in POSTGRES_TEST_URL"]
+fn postgres_store() {
+    let url = std::env::var("POSTGRES_TEST_URL").expect("POSTGRES_TEST_URL has to be set to test the Postgres store");
+
+    let store = PostgresStore::connect(&url).expect("Failed to connect to Postgres");
+    check_store(&store);
+}
+
+fn check_store(store: &dyn JobStore) {
+    insert_and_get(store);
+    status_updates(store);
+    lease_tokens(store);
+    attempts_after_requeue(store);
+    dead_letters(store);
+    calendars(store);
+    schedule_updates(store);
+    schedule_runs(store);
+}
+
+fn insert_and_get(store: &dyn JobStore) {
+    let job = test_job();
+    store.insert_job(job.clone()).unwrap();
+
+    let stored = store.get_job(job.id).unwrap().expect("Inserted job is missing");
+    assert_eq!(stored.id, job.id);
+    assert_eq!(stored.command, job.command);
+    assert_eq!(stored.args, job.args);
+    assert_eq!(stored.status, JobStatus::PENDING);
+    assert_eq!(stored.priority, Priority::HIGH);
+    assert_eq!(stored.retry_policy, job.retry_policy);
+    assert_eq!(stored.depends_on, job.depends_on);
+
+    assert!(store.get_job(Uuid::new_v4()).unwrap().is_none());
+}
+
+fn status_updates(store: &dyn JobStore) {
+    let job = test_job();
+    store.insert_job(job.clone()).unwrap();
+
+    store.update_job_status(job.id, JobStatus::RETRYING).unwrap();
+    store.update_retry_count(job.id, 2).unwrap();
+    store.update_next_attempt(job.id, at(60)).unwrap();
+
+    let stored = store.get_job(job.id).unwrap().unwrap();
+    assert_eq!(stored.status, JobStatus::RETRYING);
+    assert_eq!(stored.retry_count, 2);
+    assert_eq!(stored.next_attempt_at, Some(at(60)));
+    assert!(store.load_unfinished_jobs().unwrap().iter().any(|j| j.id == job.id));
+
+    store.update_job_status(job.id, JobStatus::COMPLETED).unwrap();
+
+    assert_eq!(store.get_job(job.id).unwrap().unwrap().status, JobStatus::COMPLETED);
+    assert!(!store.load_unfinished_jobs().unwrap().iter().any(|j| j.id == job.id));
+    assert!(store.get_job_list(Some(JobStatus::COMPLETED)).unwrap().iter().any(|j| j.id == job.id));
+    assert!(!store.get_job_list(Some(JobStatus::PENDING)).unwrap().iter().any(|j| j.id == job.id));
+}
+
+fn lease_tokens(store: &dyn JobStore) {
+    let job = test_job();
+    store.insert_job(job.clone()).unwrap();
+
+    let token = store.max_lease_token().unwrap() + 5;
+    store.update_job_lease(job.id, token, at(30)).unwrap();
+
+    assert_eq!(store.max_lease_token().unwrap(), token);
+
+    let stored = store.get_job(job.id).unwrap().unwrap();
+    assert_eq!(stored.lease_token, Some(token));
+    assert_eq!(stored.lease_expires_at, Some(at(30)));
+}
+
+fn attempts_after_requeue(store: &dyn JobStore) {
+    let job = test_job();
+    let worker_id = Uuid::new_v4();
+    store.insert_job(job.clone()).unwrap();
+
+    store.insert_attempt(job.id, worker_id, at(0), at(1), test_result(1)).unwrap();
+    store.insert_results(job.id, test_result(1)).unwrap();
+    store.update_job_status(job.id, JobStatus::FAILED).unwrap();
+
+    // Requeued from the dead letter queue, the retry count starts over but the attempts don't
+    store.update_job_status(job.id, JobStatus::PENDING).unwrap();
+    store.update_retry_count(job.id, 0).unwrap();
+    store.insert_attempt(job.id, worker_id, at(2), at(3), test_result(0)).unwrap();
+    store.insert_results(job.id, test_result(0)).unwrap();
+
+    let attempts = store.get_attempts(job.id).unwrap();
+    assert_eq!(attempts.iter().map(|a| a.attempt).collect::<Vec<_>>(), vec![1, 2]);
+    assert_eq!(attempts[0].result.exitcode, 1);
+    assert_eq!(attempts[1].worker_id, worker_id);
+    assert_eq!(attempts[1].started_at, at(2));
+
+    assert_eq!(store.get_result(job.id).unwrap().expect("Result is missing").exitcode, 0);
+    assert!(store.get_attempts(Uuid::new_v4()).unwrap().is_empty());
+}
+
+fn dead_letters(store: &dyn JobStore) {
+    let job = test_job();
+    store.insert_job(job.clone()).unwrap();
+    store.insert_attempt(job.id, Uuid::new_v4(), at(0), at(1), test_result(1)).unwrap();
+    store.update_job_status(job.id, JobStatus::FAILED).unwrap();
+
+    store.insert_dead_letter(job.id, String::from("Out of retries"), at(1)).unwrap();
+
+    let letters = store.get_dead_letters().unwrap();
+    let letter = letters.iter().find(|l| l.job.id == job.id).expect("Dead letter is missing");
+    assert_eq!(letter.reason, "Out of retries");
+    assert_eq!(letter.failed_at, at(1));
+    assert_eq!(letter.attempts.len(), 1);
+
+    assert!(store.delete_dead_letter(job.id).unwrap());
+    assert!(!store.delete_dead_letter(job.id).unwrap());
+    assert!(!store.get_dead_letters().unwrap().iter().any(|l| l.job.id == job.id));
+}
+
+fn calendars(store: &dyn JobStore) {
+    let name = format!("test-{}", Uuid::new_v4());
+    let range = BlackoutWindow::RANGE { start: at(0), end: at(3600) };
+    let recurring = BlackoutWindow::RECURRING { cron: String::from("0 0 2 * * *"), duration_secs: 600, timezone: Some(String::from("Europe/Berlin")) };
+
+    store.upsert_calendar(&Calendar { name: name.clone(), windows: vec![range.clone()] }).unwrap();
+    assert_eq!(find_calendar(store, &name).expect("Calendar is missing").windows.len(), 1);
+
+    // Upserting the same name replaces its windows
+    store.upsert_calendar(&Calendar { name: name.clone(), windows: vec![range, recurring] }).unwrap();
+
+    let calendars = store.get_calendars().unwrap();
+    assert_eq!(calendars.iter().filter(|c| c.name == name).count(), 1);
+
+    let calendar = find_calendar(store, &name).unwrap();
+    assert_eq!(calendar.windows.len(), 2);
+    assert!(matches!(&calendar.windows[1], BlackoutWindow::RECURRING { duration_secs: 600, timezone: Some(tz), .. } if tz == "Europe/Berlin"));
+
+    assert!(store.delete_calendar(&name).unwrap());
+    assert!(!store.delete_calendar(&name).unwrap());
+    assert!(find_calendar(store, &name).is_none());
+}
+
+fn schedule_updates(store: &dyn JobStore) {
+    let schedule = test_schedule();
+    store.insert_job(schedule.clone()).unwrap();
+
+    store.update_schedule_run(schedule.id, at(7200)).unwrap();
+    store.update_run_count(schedule.id, 3).unwrap();
+
+    let stored = store.get_job(schedule.id).unwrap().unwrap();
+    assert_eq!(stored.next_run, Some(at(7200)));
+    assert_eq!(stored.run_count, 3);
+    assert_eq!(stored.schedule, schedule.schedule);
+    assert!(stored.is_recurring);
+
+    // Switched from a cron to an interval
+    let updated = Job {
+        command: String::from("date"),
+        args: vec![],
+        priority: Priority::LOW,
+        schedule: None,
+        interval_secs: Some(90),
+        anchor: Some(at(0)),
+        next_run: Some(at(90)),
+        ..stored
+    };
+    store.update_schedule(&updated).unwrap();
+
+    let stored = store.get_job(schedule.id).unwrap().unwrap();
+    assert_eq!(stored.command, "date");
+    assert!(stored.args.is_empty());
+    assert_eq!(stored.priority, Priority::LOW);
+    assert_eq!(stored.schedule, None);
+    assert_eq!(stored.interval_secs, Some(90));
+    assert_eq!(stored.anchor, Some(at(0)));
+    assert_eq!(stored.next_run, Some(at(90)));
+    assert_eq!(stored.run_count, 3);
+}
+
+fn schedule_runs(store: &dyn JobStore) {
+    let schedule = test_schedule();
+    store.insert_job(schedule.clone()).unwrap();
+
+    let finished = test_run(&schedule, at(0), at(10));
+    let pending = test_run(&schedule, at(60), at(70));
+    store.insert_job(finished.clone()).unwrap();
+    store.insert_job(pending.clone()).unwrap();
+    // Not one of its runs
+    store.insert_job(test_job()).unwrap();
+
+    store.insert_attempt(finished.id, Uuid::new_v4(), at(11), at(13), test_result(1)).unwrap();
+    store.insert_attempt(finished.id, Uuid::new_v4(), at(20), at(25), test_result(2)).unwrap();
+    store.insert_results(finished.id, test_result(2)).unwrap();
+    store.update_job_status(finished.id, JobStatus::FAILED).unwrap();
+
+    // Newest first
+    let runs = store.get_schedule_runs(schedule.id, 10).unwrap();
+    assert_eq!(runs.iter().map(|r| r.job_id).collect::<Vec<_>>(), vec![pending.id, finished.id]);
+
+    let run = &runs[0];
+    assert_eq!(run.status, JobStatus::PENDING);
+    assert_eq!(run.scheduled_for, Some(at(60)));
+    assert_eq!(run.attempts, 0);
+    assert_eq!(run.exitcode, None);
+    assert_eq!((run.started_at, run.finished_at, run.duration_ms), (None, None, None));
+
+    let run = &runs[1];
+    assert_eq!(run.status, JobStatus::FAILED);
+    assert_eq!(run.created_at, at(10));
+    assert_eq!(run.scheduled_for, Some(at(0)));
+    assert_eq!(run.attempts, 2);
+    assert_eq!(run.exitcode, Some(2));
+    assert_eq!(run.started_at, Some(at(11)));
+    assert_eq!(run.finished_at, Some(at(25)));
+    assert_eq!(run.duration_ms, Some(14_000));
+
+    let runs = store.get_schedule_runs(schedule.id, 1).unwrap();
+    assert_eq!(runs.iter().map(|r| r.job_id).collect::<Vec<_>>(), vec![pending.id]);
+}
+
+fn find_calendar(store: &dyn JobStore, name: &str) -> Option<Calendar> {
+    store.get_calendars().unwrap().into_iter().find(|c| c.name == name)
+}
+
+// Whole seconds so every backend gives back exactly what it was given
+fn at(secs: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(1_800_000_000, 0).unwrap() + Duration::seconds(secs)
+}
+
+fn test_result(exitcode: i32) -> JobResult {
+    JobResult {
+        exitcode,
+        stdout: format!("exit {}\n", exitcode),
+        stderr: String::new(),
+        timed_out: false
+    }
+}
+
+fn test_schedule() -> Job {
+    Job {
+        schedule: Some(String::from("0 0 * * * *")),
+        next_run: Some(at(3600)),
+        is_recurring: true,
+        depends_on: None,
+        ..test_job()
+    }
+}
+
+fn test_run(schedule: &Job, scheduled_for: DateTime<Utc>, created_at: DateTime<Utc>) -> Job {
+    Job {
+        timestamp: created_at,
+        parent_schedule_id: Some(schedule.id),
+        scheduled_for: Some(scheduled_for),
+        depends_on: None,
+        ..test_job()
+    }
+}
+
+fn test_job() -> Job {
+    Job {
+        id: Uuid::new_v4(),
+        command: String::from("echo"),
+        args: vec![String::from("store"), String::from("test")],
+        status: JobStatus::PENDING,
+        timestamp: Utc::now(),
+
+        retry_count: 0,
+        max_retries: 3,
+        retry_policy: Default::default(),
+        next_attempt_at: None,
+
+        priority: Priority::HIGH,
+
+        schedule: None,
+        interval_secs: None,
+        anchor: None,
+        jitter_secs: None,
+        timezone: None,
+        misfire_policy: Default::default(),
+        max_catchup: 0,
+        concurrency_policy: Default::default(),
+        not_before: None,
+        not_after: None,
+        max_runs: None,
+        run_count: 0,
+        calendars: None,
+        blackout_policy: Default::default(),
+        next_run: None,
+        is_recurring: false,
+        parent_schedule_id: None,
+        scheduled_for: None,
+
+        depends_on: Some(vec![Uuid::new_v4()]),
+
+        run_at: None,
+
+        timeout_secs: None,
+
+        env: None,
+
+        lease_token: None,
+        lease_expires_at: None
+    }
+}
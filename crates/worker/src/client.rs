@@ -1,10 +1,12 @@
 use common::{
     message::{
+        JobLease,
+        JobResultReport,
         WorkerHeartbeat, 
+        WorkerHeartbeatResponse,
         NextJobRequest, 
         WorkerRegister
-    },
-    job::JobResult
+    }
 };
 use reqwest::{
     Error, Response, StatusCode
@@ -52,12 +54,14 @@ pub async fn register_worker(worker: WorkerRegister) {
     }
 }
 
-pub async fn send_heartbeat(worker_id: Uuid, worker: &WorkerRegister) {
+// Returns the ids of jobs the coordinator wants killed
+pub async fn send_heartbeat(worker_id: Uuid, worker: &WorkerRegister, leases: Vec<JobLease>) -> Vec<Uuid> {
     let url = format!("http://{}/api/worker/heartbeat", *COORDINATOR_ADDR);
 
     let heartbeat = WorkerHeartbeat {
         worker_id,
-        timestamp: Utc::now()
+        timestamp: Utc::now(),
+        leases
     };
 
     let client = reqwest::Client::new();
@@ -72,10 +76,16 @@ pub async fn send_heartbeat(worker_id: Uuid, worker: &WorkerRegister) {
             if response.status() == StatusCode::NOT_FOUND {
                 log::info!("Detected that worker isn't connected to coordinator. Re-regestering.");
                 register_worker(worker.clone()).await;
+                vec![]
+            } else {
+                response.json::<WorkerHeartbeatResponse>().await
+                    .map(|r| r.cancel_jobs)
+                    .unwrap_or_default()
             }
         },
         Err(_) => {
-            log::error!("Failed to send heartbeat to coordinator")
+            log::error!("Failed to send heartbeat to coordinator");
+            vec![]
         }
     }
 }
@@ -97,25 +107,31 @@ pub async fn get_next_job(worker_id: Uuid) -> Result<Response, Error> {
 }
 
 // Only loop 3 times (Or what MAX_RETRIES is set to) then we can assume coordinator is offline or networking error and log the job_id w/result
-pub async fn post_job_results(results: JobResult, job_id: Uuid) {
+pub async fn post_job_results(report: JobResultReport) {
     for i in 1..=MAX_RETRIES {
-        let url = format!("http://{}/api/job/{}/results", *COORDINATOR_ADDR, job_id);
+        let url = format!("http://{}/api/job/{}/results", *COORDINATOR_ADDR, report.job_id);
 
         let client = reqwest::Client::new();
 
         match client.post(url)
             .header("Content-Type", "application/json")
-            .json(&results)
+            .json(&report)
             .send()
             .await 
             {
-            Ok(_) => {break;/* Break out of the loop as results got submited */},
+            Ok(response) => {
+                // The coordinator gave the job to someone else after our lease ran out, these results are stale
+                if response.status() == StatusCode::CONFLICT {
+                    log::warn!("Coordinator rejected results for job {} as lease {} is no longer valid", report.job_id, report.lease_token);
+                }
+                break;/* Break out of the loop as results got submited */
+            },
             Err(_) => {
                 if i != MAX_RETRIES {
-                    log::error!("Failed to submit results for job with ID: {}. Retrying after 10 seconds!", job_id);
+                    log::error!("Failed to submit results for job with ID: {}. Retrying after 10 seconds!", report.job_id);
                     sleep(Duration::from_secs(10)).await;
                 } else {
-                    log::error!("Failed to submit results for job after {} retries.\nJob: {}\nResults: {:#?}", MAX_RETRIES, job_id, results)
+                    log::error!("Failed to submit results for job after {} retries.\nJob: {}\nResults: {:#?}", MAX_RETRIES, report.job_id, report.job_result)
                 }
             }
         }
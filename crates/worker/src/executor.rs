@@ -1,60 +1,130 @@
 use common::job::{
-    JobResult, 
+    JobResult,
     Job
 };
-use tokio::process::Command;
+use std::{
+    future,
+    process::Stdio,
+    time::Duration
+};
+use tokio::{
+    process::Command,
+    sync::oneshot,
+    time::sleep
+};
 
 const FAILED_MESSAGE: &str = "The command has failed. Check permission or if command exist.";
+const CANCELED_MESSAGE: &str = "The job was canceled by the coordinator.";
 
-pub async fn execute(job: Job) -> JobResult {
-    #[cfg(target_os = "windows")] {
+// `cancel` fires when the coordinator asks us to kill the job
+pub async fn execute(job: Job, cancel: oneshot::Receiver<()>) -> JobResult {
+    #[cfg(target_os = "windows")]
+    let mut cmd = {
         let mut cmd = Command::new("cmd");
         cmd.args(["/C", &job.command]);
+        cmd
+    };
 
-        cmd.args(&job.args);
-        let output = cmd.output().await;
+    #[cfg(not(target_os = "windows"))]
+    let mut cmd = {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", &job.command]);
 
-        match output {
-            Ok(o) => {
-                return JobResult { 
-                    exitcode: o.status.code().unwrap_or(-1), 
-                    stdout: String::from_utf8_lossy(&o.stdout).to_string(), 
-                    stderr: String::from_utf8_lossy(&o.stderr).to_string()
-                }
-            },
-            Err(_) => {
-                return JobResult {
-                    exitcode: 1, 
-                    stdout: String::from(""), 
-                    stderr: String::from(FAILED_MESSAGE)
-                }
+        // Own process group so anything the shell spawns can be killed with it
+        cmd.process_group(0);
+        cmd
+    };
+
+    if let Some(env) = &job.env {
+        cmd.envs(env);
+    }
+
+    // Lets a late or backfilled run work on the window it was scheduled for rather than the current time
+    if let Some(scheduled_for) = job.scheduled_for {
+        cmd.env("SCHEDULER_LOGICAL_TIME", scheduled_for.to_rfc3339());
+    }
+
+    cmd.args(&job.args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    let child = match cmd.spawn() {
+        Ok(c) => c,
+        Err(_) => {
+            return JobResult {
+                exitcode: 1,
+                stdout: String::from(""),
+                stderr: String::from(FAILED_MESSAGE),
+                timed_out: false
             }
         }
-    }
+    };
 
-    #[cfg(not(target_os = "windows"))] {
-        let mut cmd = Command::new("sh");
-        cmd.args(["-c", &job.command]);
+    let pid = child.id();
 
-        cmd.args(&job.args);
-        let output = cmd.output().await;
+    let timeout = async {
+        match job.timeout_secs {
+            Some(secs) => sleep(Duration::from_secs(secs)).await,
+            None => future::pending().await
+        }
+    };
 
-        match output {
-            Ok(o) => {
-                return JobResult { 
-                    exitcode: o.status.code().unwrap_or(-1), 
-                    stdout: String::from_utf8_lossy(&o.stdout).to_string(), 
-                    stderr: String::from_utf8_lossy(&o.stderr).to_string()
-                }
-            },
-            Err(_) => {
-                return JobResult {
-                    exitcode: 1, 
-                    stdout: String::from(""), 
-                    stderr: String::from(FAILED_MESSAGE)
+    tokio::select! {
+        output = child.wait_with_output() => {
+            match output {
+                Ok(o) => {
+                    JobResult {
+                        exitcode: o.status.code().unwrap_or(-1),
+                        stdout: String::from_utf8_lossy(&o.stdout).to_string(),
+                        stderr: String::from_utf8_lossy(&o.stderr).to_string(),
+                        timed_out: false
+                    }
+                },
+                Err(_) => {
+                    JobResult {
+                        exitcode: 1,
+                        stdout: String::from(""),
+                        stderr: String::from(FAILED_MESSAGE),
+                        timed_out: false
+                    }
                 }
             }
+        },
+        Ok(_) = cancel => {
+            log::warn!("Killing job {} as it was canceled", job.id);
+            kill_process_group(pid);
+
+            JobResult {
+                exitcode: -1,
+                stdout: String::from(""),
+                stderr: String::from(CANCELED_MESSAGE),
+                timed_out: false
+            }
+        },
+        _ = timeout => {
+            log::warn!("Killing job {} as it ran longer than {:?} seconds", job.id, job.timeout_secs);
+            kill_process_group(pid);
+
+            JobResult {
+                exitcode: -1,
+                stdout: String::from(""),
+                stderr: format!("The job timed out after {} seconds.", job.timeout_secs.unwrap_or_default()),
+                timed_out: true
+            }
+        }
+    }
+}
+
+// The child itself is killed when dropped, this takes down whatever it spawned too
+#[cfg(unix)]
+fn kill_process_group(pid: Option<u32>) {
+    if let Some(pid) = pid {
+        unsafe {
+            libc::kill(-(pid as i32), libc::SIGKILL);
         }
     }
-    
-}
\ No newline at end of file
+}
+
+#[cfg(not(unix))]
+fn kill_process_group(_pid: Option<u32>) {}
@@ -1,12 +1,25 @@
 use common::{
-    message::WorkerRegister,
+    message::{
+        JobLease,
+        JobResultReport,
+        WorkerRegister
+    },
     job::Job, 
 };
-use tokio::time::{
-    sleep,
-    self, 
+use chrono::Utc;
+use tokio::{
+    sync::{oneshot, Semaphore},
+    time::{
+        sleep,
+        self, 
+    }
+};
+use std::{
+    collections::HashMap, 
+    str::FromStr,
+    sync::{Arc, LazyLock, Mutex}, 
+    time::Duration
 };
-use std::time::Duration;
 use uuid::Uuid;
 
 use crate::executor::execute;
@@ -15,6 +28,30 @@ mod client; mod executor;
 
 const HEARTBEAT_INTERVAL: u64 = 10;
 
+static WORKER_CONCURRENCY: LazyLock<usize> = LazyLock::new(|| {
+    let concurrency = std::env::var("WORKER_CONCURRENCY");
+    match concurrency {
+        Ok(slots) => {
+            usize::from_str(&slots).ok().filter(|s| *s > 0).unwrap_or_else(|| {
+                log::info!("Failed to parse WORKER_CONCURRENCY. Defaulting to 1 job at a time.");
+                1
+            })
+        },
+        Err(_) => {
+            log::info!("WORKER_CONCURRENCY is not present. Defaulting to 1 job at a time.");
+            1
+        }
+    }
+});
+
+struct RunningJob {
+    lease_token: u64,
+    kill: oneshot::Sender<()>
+}
+
+// Leases and kill switches for the jobs currently running on this worker
+type RunningJobs = Arc<Mutex<HashMap<Uuid, RunningJob>>>;
+
 #[tokio::main]
 async fn main() {
     dotenvy::dotenv().ok();
@@ -24,29 +61,70 @@ async fn main() {
     let hostname = hostname::get().unwrap_or_default().to_string_lossy().to_string();
     let worker_id = Uuid::new_v4();
 
-    let worker = WorkerRegister {worker_id, hostname: hostname.clone()};
+    let worker = WorkerRegister {worker_id, hostname: hostname.clone(), capacity: *WORKER_CONCURRENCY};
 
     client::register_worker(worker.clone()).await;
 
-    log::info!("Registered with coordinator with ID {} and hostname {}", worker_id, hostname);
+    log::info!("Registered with coordinator with ID {} and hostname {}, running up to {} jobs at once", worker_id, hostname, *WORKER_CONCURRENCY);
+
+    let running: RunningJobs = Arc::new(Mutex::new(HashMap::new()));
 
+    let heartbeat_running = running.clone();
     tokio::spawn(async move {
         loop {
-            client::send_heartbeat(worker_id, &worker).await;
+            let leases = heartbeat_running.lock().unwrap().iter()
+                .map(|(job_id, running)| JobLease { job_id: *job_id, lease_token: running.lease_token })
+                .collect();
+
+            let cancel_jobs = client::send_heartbeat(worker_id, &worker, leases).await;
+
+            for job_id in cancel_jobs {
+                if let Some(running) = heartbeat_running.lock().unwrap().remove(&job_id) {
+                    log::info!("Coordinator canceled job {}", job_id);
+                    let _ = running.kill.send(());
+                }
+            }
+
             sleep(Duration::from_secs(HEARTBEAT_INTERVAL)).await;
         }
     });
 
+    // One permit per slot, a permit is held for as long as its job runs
+    let slots = Arc::new(Semaphore::new(*WORKER_CONCURRENCY));
+
     loop {
-        match client::get_next_job(worker_id.clone()).await {
+        let permit = slots.clone().acquire_owned().await.expect("Job slot semaphore closed");
+
+        match client::get_next_job(worker_id).await {
             Ok(response) => {
                 if response.status().is_success() {
                     match response.json::<Job>().await {
                         Ok(job) => {
                             log::info!("Got job: {:?}", job);
-                            let results = execute(job.clone()).await;
-                            log::info!("Sending result to coordinator");
-                            client::post_job_results(results, job.id).await;
+
+                            let lease_token = job.lease_token.unwrap_or_default();
+
+                            let (kill, cancel) = oneshot::channel();
+                            running.lock().unwrap().insert(job.id, RunningJob { lease_token, kill });
+
+                            let job_running = running.clone();
+                            tokio::spawn(async move {
+                                let started_at = Utc::now();
+                                let results = execute(job.clone(), cancel).await;
+                                job_running.lock().unwrap().remove(&job.id);
+
+                                log::info!("Sending result to coordinator");
+                                client::post_job_results(JobResultReport {
+                                    job_id: job.id,
+                                    worker_id,
+                                    job_result: results,
+                                    started_at,
+                                    finished_at: Utc::now(),
+                                    lease_token
+                                }).await;
+
+                                drop(permit);
+                            });
                         }
                         Err(e) => log::error!("Failed to parse job: {}", e),
                     }